@@ -56,6 +56,20 @@ export function activate(context: ExtensionContext) {
 		clientOptions
 	);
 
+	// Code lens arguments are protocol types, editor command needs vscode types.
+	context.subscriptions.push(vscode.commands.registerCommand(
+		"jinja-lsp.showReferences",
+		(uri: string, position: any, locations: any[]) => {
+			const converter = client.protocol2CodeConverter;
+			return vscode.commands.executeCommand(
+				"editor.action.showReferences",
+				converter.asUri(uri),
+				converter.asPosition(position),
+				locations.map(location => converter.asLocation(location)),
+			);
+		}
+	));
+
 	// Start the client. This will also launch the server
 	client.start();
 }
@@ -67,7 +67,7 @@ impl NodejsLspFiles {
     Self {
       lsp_files: lsp_files2,
       counter: 0,
-      filters: init_filter_completions(),
+      filters: init_filter_completions("python"),
       _snippets: snippets(),
       actions: HashMap::new(),
       action_objects: HashMap::new(),
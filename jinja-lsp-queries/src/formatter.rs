@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+
+use tree_sitter::{Node, Point, Tree};
+
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// String used for one level of indentation.
+    pub indent: String,
+    /// Adds one space after opening and before closing delimiter.
+    pub delimiter_padding: bool,
+    /// Visual width of tab character.
+    pub tab_size: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent: String::from("    "),
+            delimiter_padding: true,
+            tab_size: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TagKind {
+    Open,
+    Middle,
+    Close,
+    Raw,
+    EndRaw,
+    Other,
+}
+
+#[derive(Debug)]
+struct Tag {
+    start: Point,
+    end: Point,
+    kind: TagKind,
+    normalized: Option<String>,
+}
+
+/// Formats template, line count stays the same so result can be compared
+/// line by line with the source. Templates with syntax errors are not formatted.
+pub fn format_template(tree: &Tree, source: &str, options: &FormatOptions) -> Option<String> {
+    let root = tree.root_node();
+    if root.has_error() {
+        return None;
+    }
+    let tags = collect_tags(root, source, options);
+    let lines: Vec<&str> = source.split('\n').collect();
+    let mut tags_by_row: HashMap<usize, Vec<&Tag>> = HashMap::new();
+    let mut verbatim = vec![false; lines.len()];
+    let mut raw_start = None;
+    for tag in &tags {
+        for row in tag.start.row + 1..=tag.end.row {
+            if let Some(row) = verbatim.get_mut(row) {
+                *row = true;
+            }
+        }
+        match (tag.kind, raw_start) {
+            (TagKind::Raw, None) => raw_start = Some(tag.end.row),
+            (TagKind::EndRaw, Some(start)) => {
+                for row in start + 1..=tag.start.row {
+                    if let Some(row) = verbatim.get_mut(row) {
+                        *row = true;
+                    }
+                }
+                raw_start = None;
+            }
+            (_, Some(_)) => continue,
+            _ => {}
+        }
+        tags_by_row.entry(tag.start.row).or_default().push(tag);
+    }
+    if let Some(start) = raw_start {
+        for row in verbatim.iter_mut().skip(start + 1) {
+            *row = true;
+        }
+    }
+
+    let unit = indent_width(&options.indent, options);
+    // Every block (and every `else` branch) starts new body. Body is shifted as
+    // a whole so that it is one level deeper than line that opened it.
+    let mut bodies = vec![Body::default()];
+    let mut stack: Vec<usize> = vec![];
+    let mut rows = vec![];
+    for (row, line) in lines.iter().enumerate() {
+        let (line, cr) = match line.strip_suffix('\r') {
+            Some(line) => (line, "\r"),
+            None => (*line, ""),
+        };
+        if verbatim[row] {
+            rows.push(Row::Verbatim(format!("{line}{cr}")));
+            continue;
+        }
+        let content = normalize_line(line, row, tags_by_row.get(&row));
+        let trimmed = content.trim_start();
+        if trimmed.is_empty() {
+            rows.push(Row::Verbatim(cr.to_string()));
+            continue;
+        }
+        let leading = &content[..content.len() - trimmed.len()];
+        let width = indent_width(leading, options);
+        let text = format!("{trimmed}{cr}");
+        let row_tags = tags_by_row
+            .get(&row)
+            .map(|tags| tags.as_slice())
+            .unwrap_or(&[]);
+        let first_tag = row_tags
+            .first()
+            .filter(|tag| tag.start.column == leading.len());
+        let current = stack.last().copied();
+        match (first_tag, current) {
+            (Some(tag), Some(body)) if matches!(tag.kind, TagKind::Close | TagKind::Middle) => {
+                rows.push(Row::Aligned(bodies[body].opener, text));
+            }
+            _ => {
+                let body = current.unwrap_or_default();
+                let min = bodies[body].min_width.get_or_insert(width);
+                *min = (*min).min(width);
+                rows.push(Row::Body(body, width, text));
+            }
+        }
+        for tag in row_tags {
+            match tag.kind {
+                TagKind::Open => {
+                    bodies.push(Body {
+                        opener: row,
+                        min_width: None,
+                    });
+                    stack.push(bodies.len() - 1);
+                }
+                TagKind::Middle => {
+                    if let Some(body) = stack.pop() {
+                        let opener = bodies[body].opener;
+                        bodies.push(Body {
+                            opener,
+                            min_width: None,
+                        });
+                        stack.push(bodies.len() - 1);
+                    }
+                }
+                TagKind::Close => {
+                    stack.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut widths = vec![0; rows.len()];
+    let mut formatted = Vec::with_capacity(rows.len());
+    for (row, line) in rows.into_iter().enumerate() {
+        let (width, text) = match line {
+            Row::Verbatim(text) => {
+                formatted.push(text);
+                continue;
+            }
+            Row::Aligned(opener, text) => (widths[opener], text),
+            Row::Body(0, width, text) => (width, text),
+            Row::Body(body, width, text) => {
+                let body = &bodies[body];
+                let min = body.min_width.unwrap_or(width);
+                (
+                    (widths[body.opener] + unit + width).saturating_sub(min),
+                    text,
+                )
+            }
+        };
+        widths[row] = width;
+        formatted.push(format!("{}{text}", indentation(width, options)));
+    }
+    Some(formatted.join("\n"))
+}
+
+#[derive(Default)]
+struct Body {
+    opener: usize,
+    min_width: Option<usize>,
+}
+
+enum Row {
+    Verbatim(String),
+    /// `else` and end tags are aligned with line that opened the block.
+    Aligned(usize, String),
+    Body(usize, usize, String),
+}
+
+/// Visual width of leading whitespace, tab is `tab_size` columns wide.
+fn indent_width(leading: &str, options: &FormatOptions) -> usize {
+    let tab = options.tab_size.max(1);
+    leading
+        .chars()
+        .map(|c| if c == '\t' { tab } else { 1 })
+        .sum()
+}
+
+fn indentation(width: usize, options: &FormatOptions) -> String {
+    let tab = options.tab_size.max(1);
+    if options.indent == "\t" {
+        format!("{}{}", "\t".repeat(width / tab), " ".repeat(width % tab))
+    } else {
+        " ".repeat(width)
+    }
+}
+
+fn collect_tags(root: Node, source: &str, options: &FormatOptions) -> Vec<Tag> {
+    let mut tags = vec![];
+    let mut cursor = root.walk();
+    for node in root.named_children(&mut cursor) {
+        let Ok(text) = node.utf8_text(source.as_bytes()) else {
+            continue;
+        };
+        let kind = match node.kind() {
+            "statement" => statement_kind(node, source),
+            "expression" | "comment" => TagKind::Other,
+            _ => continue,
+        };
+        let start = node.start_position();
+        let end = node.end_position();
+        let normalized = match node.kind() {
+            "statement" | "expression" if start.row == end.row => normalize_tag(text, options),
+            _ => None,
+        };
+        tags.push(Tag {
+            start,
+            end,
+            kind,
+            normalized,
+        });
+    }
+    tags
+}
+
+fn statement_kind(node: Node, source: &str) -> TagKind {
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.named_children(&mut cursor).collect();
+    let Some(keyword) = children.iter().find(|child| child.kind() == "keyword") else {
+        return TagKind::Other;
+    };
+    let keyword = keyword.utf8_text(source.as_bytes()).unwrap_or_default();
+    match keyword {
+        "if" | "for" | "block" | "macro" | "call" | "filter" | "with" | "autoescape" | "trans" => {
+            TagKind::Open
+        }
+        "set" => {
+            let assignment = children
+                .iter()
+                .any(|child| child.kind() == "equal_operator");
+            if assignment {
+                TagKind::Other
+            } else {
+                TagKind::Open
+            }
+        }
+        "elif" | "else" | "pluralize" => TagKind::Middle,
+        "endif" | "endfor" | "endblock" | "endmacro" | "endcall" | "endfilter" | "endwith"
+        | "endset" | "endautoescape" | "endtrans" => TagKind::Close,
+        "raw" => TagKind::Raw,
+        "endraw" => TagKind::EndRaw,
+        _ => TagKind::Other,
+    }
+}
+
+/// `{{x}}` and `{%-  if x %}` become `{{ x }}` and `{%- if x %}`, whitespace
+/// control markers are kept next to delimiters.
+fn normalize_tag(text: &str, options: &FormatOptions) -> Option<String> {
+    let open = text.get(..2)?;
+    let close = text.get(text.len().checked_sub(2)?..)?;
+    if !matches!((open, close), ("{{", "}}") | ("{%", "%}")) {
+        return None;
+    }
+    let mut inner = text.get(2..text.len() - 2)?;
+    let mut open_marker = "";
+    let mut close_marker = "";
+    if let Some(rest) = inner.strip_prefix(['-', '+']) {
+        open_marker = &inner[..1];
+        inner = rest;
+    }
+    if let Some(rest) = inner.strip_suffix(['-', '+']) {
+        close_marker = &inner[inner.len() - 1..];
+        inner = rest;
+    }
+    let inner = inner.trim();
+    if inner.is_empty() {
+        return None;
+    }
+    let padding = if options.delimiter_padding { " " } else { "" };
+    Some(format!(
+        "{open}{open_marker}{padding}{inner}{padding}{close_marker}{close}"
+    ))
+}
+
+fn normalize_line(line: &str, row: usize, tags: Option<&Vec<&Tag>>) -> String {
+    let mut line = line.to_string();
+    let Some(tags) = tags else {
+        return line;
+    };
+    for tag in tags.iter().rev() {
+        let Some(normalized) = &tag.normalized else {
+            continue;
+        };
+        if tag.end.row != row || line.get(tag.start.column..tag.end.column).is_none() {
+            continue;
+        }
+        line.replace_range(tag.start.column..tag.end.column, normalized);
+    }
+    line
+}
@@ -1,3 +1,5 @@
+pub mod builtins;
+pub mod formatter;
 pub mod lsp_helper;
 pub mod parsers;
 pub mod search;
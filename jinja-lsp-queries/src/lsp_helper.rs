@@ -1,13 +1,27 @@
-use std::{collections::HashMap, io::ErrorKind, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
 
-use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticSeverity, DocumentLink, DocumentSymbol, FoldingRange, FoldingRangeKind,
+    Location, Position, Range, Url,
+};
 use tree_sitter::{Point, Tree};
 
 use crate::{
     search::{
-        Identifier, IdentifierType, objects::objects_query, queries::Queries,
-        templates::templates_query,
+        Identifier, IdentifierType,
+        definition::definition_query,
+        objects::{JinjaObject, objects_query},
+        queries::Queries,
+        syntax::syntax_errors,
+        template_graph::{EdgeKind, TemplateGraph},
+        templates::{Import, templates_query},
+        to_range,
     },
+    to_input_edit::to_position2,
     tree_builder::{JinjaDiagnostic, LangType},
 };
 
@@ -21,63 +35,47 @@ pub fn search_errors(
     templates: PathBuf,
     lang_type: LangType,
     ignore_globals: bool,
+    context: Option<&HashSet<String>>,
 ) -> Option<Vec<(JinjaDiagnostic, Identifier)>> {
     let mut diagnostics = vec![];
     match lang_type {
         LangType::Template => {
             let trigger_point = Point::new(0, 0);
-            let query = &queries.jinja_objects;
-            let objects = objects_query(query, root, trigger_point, source, true);
-            let objects1 = objects.objects;
+            diagnostics.extend(syntax_errors(root, source));
             let this_file = variables.get(file_name)?;
-            for object in objects1 {
-                if object.is_filter || object.is_test {
+            for object in undefined_objects(root, source, queries, this_file) {
+                if ignore_globals {
+                    continue;
+                }
+                if context.is_some_and(|context| context.contains(&object.name)) {
                     continue;
                 }
                 let mut err_type = JinjaDiagnostic::Undefined;
-                let located = this_file
-                    .iter()
-                    .filter(|variable| {
-                        variable.name == object.name
-                            && variable.identifier_type != IdentifierType::TemplateBlock
-                    })
-                    .filter(|file_variable| {
-                        let object_location = object.location();
-                        let can_be_used = object_location.1 >= file_variable.start;
-                        let in_scope = object_location.0 < file_variable.scope_ends.1;
-                        can_be_used && in_scope
-                    });
-                let empty = located.count() == 0;
-                let mut to_warn = false;
-                if empty {
-                    if ignore_globals {
+                let mut to_warn = true;
+                let mut count = 0;
+                for file in variables {
+                    if file.0 == file_name {
                         continue;
                     }
-                    to_warn = true;
-                    let mut count = 0;
-                    for file in variables {
-                        if file.0 == file_name {
-                            continue;
-                        }
-                        let idents = file
-                            .1
-                            .iter()
-                            .filter(|variable| variable.name == object.name);
-                        count += idents.count();
-                        if count > 1 {
-                            err_type = JinjaDiagnostic::DefinedInMultiplePlaces;
-                            to_warn = true;
-                            break;
-                        }
-                    }
-                    if count == 1 {
-                        to_warn = false;
+                    let idents = file
+                        .1
+                        .iter()
+                        .filter(|variable| variable.name == object.name)
+                        .filter(|variable| {
+                            // only render calls of this template can define it
+                            context.is_none()
+                                || variable.identifier_type != IdentifierType::BackendVariable
+                        });
+                    count += idents.count();
+                    if count > 1 {
+                        err_type = JinjaDiagnostic::DefinedInMultiplePlaces;
+                        break;
                     }
                 }
+                if count == 1 {
+                    to_warn = false;
+                }
                 if to_warn {
-                    if ignore_globals {
-                        continue;
-                    }
                     let diagnostic = (err_type, Identifier::from(&object));
                     diagnostics.push(diagnostic);
                 }
@@ -130,6 +128,146 @@ pub fn search_errors(
     }
 }
 
+/// Objects used in template that aren't defined in it, filters and tests are
+/// skipped.
+pub fn undefined_objects(
+    root: &Tree,
+    source: &str,
+    queries: &Queries,
+    this_file: &[Identifier],
+) -> Vec<JinjaObject> {
+    let query = &queries.jinja_objects;
+    let objects = objects_query(query, root, Point::new(0, 0), source, true);
+    objects
+        .objects
+        .into_iter()
+        .filter(|object| !object.is_filter && !object.is_test)
+        .filter(|object| {
+            let object_location = object.location();
+            !this_file.iter().any(|variable| {
+                let can_be_used = object_location.1 >= variable.start;
+                let in_scope = object_location.0 < variable.scope_ends.1;
+                variable.name == object.name
+                    && variable.identifier_type != IdentifierType::TemplateBlock
+                    && can_be_used
+                    && in_scope
+            })
+        })
+        .collect()
+}
+
+/// Names that template reads from backend context. Blocks, macros and names
+/// imported from other templates are not variables.
+pub fn context_names(
+    root: &Tree,
+    source: &str,
+    queries: &Queries,
+    this_file: &[Identifier],
+) -> Vec<String> {
+    let query = &queries.jinja_imports;
+    let imports = templates_query(query, root, Point::new(0, 0), source, true).show();
+    let mut defined: HashSet<&str> = this_file
+        .iter()
+        .filter(|variable| {
+            matches!(
+                variable.identifier_type,
+                IdentifierType::TemplateBlock | IdentifierType::MacroName
+            )
+        })
+        .map(|variable| variable.name.as_str())
+        .collect();
+    for import in &imports {
+        match import {
+            Import::Import { identifier, .. } => {
+                defined.insert(&identifier.name);
+            }
+            Import::From { .. } => {
+                for (name, alias) in import.imported_names() {
+                    defined.insert(&name.name);
+                    defined.extend(alias.map(|alias| alias.name.as_str()));
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut names: Vec<String> = vec![];
+    for object in undefined_objects(root, source, queries, this_file) {
+        if !defined.contains(object.name.as_str()) && !names.contains(&object.name) {
+            names.push(object.name);
+        }
+    }
+    names
+}
+
+/// Filters and tests used in template that aren't in `filters` and `tests`,
+/// with the closest known name as suggestion.
+pub fn unknown_filters(
+    root: &Tree,
+    source: &str,
+    queries: &Queries,
+    filters: &[String],
+    tests: &[String],
+) -> Vec<(JinjaDiagnostic, Identifier)> {
+    let query = &queries.jinja_objects;
+    let objects = objects_query(query, root, Point::new(0, 0), source, true);
+    let mut diagnostics = vec![];
+    for object in objects.objects {
+        let known = match (object.is_filter, object.is_test) {
+            (true, _) => filters,
+            (_, true) => tests,
+            _ => continue,
+        };
+        if known.contains(&object.name) {
+            continue;
+        }
+        let suggestion = closest_name(&object.name, known);
+        let diagnostic = if object.is_filter {
+            JinjaDiagnostic::UnknownFilter(suggestion)
+        } else {
+            JinjaDiagnostic::UnknownTest(suggestion)
+        };
+        diagnostics.push((diagnostic, Identifier::from(&object)));
+    }
+    diagnostics
+}
+
+/// Name from `names` that is only few edits away from `name`.
+pub fn closest_name(name: &str, names: &[String]) -> Option<String> {
+    let allowed = (name.chars().count() / 3).max(1);
+    names
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= allowed)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.to_owned())
+}
+
+/// Edit distance between two names, swapped neighbours count as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
 pub fn create_diagnostic(
     template: &Identifier,
     severity: DiagnosticSeverity,
@@ -150,3 +288,300 @@ pub fn create_diagnostic(
 pub fn path_items(template: &str) -> PathBuf {
     template.split('/').collect()
 }
+
+/// Path of template relative to templates directory, as it is written in
+/// `{% include %}` or in `render_template`.
+pub fn template_name(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let mut parts = vec![];
+    for component in relative.components() {
+        parts.push(component.as_os_str().to_str()?);
+    }
+    Some(parts.join("/"))
+}
+
+/// Name of template at `path` after files or folders in `renamed` are moved,
+/// `None` when template isn't moved.
+pub fn renamed_template(
+    root: &Path,
+    path: &Path,
+    renamed: &[(PathBuf, PathBuf)],
+) -> Option<String> {
+    let new_path = renamed.iter().find_map(|(old, new)| {
+        let rest = path.strip_prefix(old).ok()?;
+        if rest.as_os_str().is_empty() {
+            Some(new.to_owned())
+        } else {
+            Some(new.join(rest))
+        }
+    })?;
+    template_name(root, &new_path)
+}
+
+/// Places that use template, by kind of use.
+#[derive(Default, Debug)]
+pub struct TemplateDependents {
+    pub rendered: Vec<Location>,
+    pub included: Vec<Location>,
+    pub imported: Vec<Location>,
+    pub extended: Vec<Location>,
+}
+
+/// Backend calls and template statements that render template `uri`.
+pub fn template_dependents(graph: &TemplateGraph, uri: &str) -> TemplateDependents {
+    let mut dependents = TemplateDependents::default();
+    for (file, call) in graph.render_calls(uri) {
+        let Ok(url) = Url::parse(file) else {
+            continue;
+        };
+        let range = to_range((call.template.start, call.template.end));
+        dependents.rendered.push(Location::new(url, range));
+    }
+    for (template, edge) in graph.dependents(uri) {
+        let Ok(url) = Url::parse(template) else {
+            continue;
+        };
+        let locations = match edge.kind {
+            EdgeKind::Extends => &mut dependents.extended,
+            EdgeKind::Include => &mut dependents.included,
+            EdgeKind::Import => &mut dependents.imported,
+        };
+        let range = to_range((edge.template.start, edge.template.end));
+        locations.push(Location::new(url, range));
+    }
+    for locations in [
+        &mut dependents.rendered,
+        &mut dependents.included,
+        &mut dependents.imported,
+        &mut dependents.extended,
+    ] {
+        locations
+            .sort_by(|a, b| (a.uri.as_str(), a.range.start).cmp(&(b.uri.as_str(), b.range.start)));
+    }
+    dependents
+}
+
+/// Lens title for every kind of use that has places, with these places.
+pub fn dependent_lenses(dependents: TemplateDependents) -> Vec<(String, Vec<Location>)> {
+    [
+        ("rendered by", "handler", dependents.rendered),
+        ("included by", "template", dependents.included),
+        ("imported by", "template", dependents.imported),
+        ("extended by", "template", dependents.extended),
+    ]
+    .into_iter()
+    .filter(|(_, _, locations)| !locations.is_empty())
+    .map(|(action, noun, locations)| {
+        let count = locations.len();
+        let plural = if count == 1 { "" } else { "s" };
+        (format!("{action} {count} {noun}{plural}"), locations)
+    })
+    .collect()
+}
+
+/// Range of template path inside of its quotes.
+pub fn template_path_range(template: &Identifier) -> Range {
+    let mut start = to_position2(template.start);
+    let mut end = to_position2(template.end);
+    start.character += 1;
+    end.character = end.character.saturating_sub(1);
+    Range::new(start, end)
+}
+
+/// Links from template paths to uri of template that `resolve` finds,
+/// paths of missing templates are skipped.
+pub fn document_links(
+    templates: Vec<Identifier>,
+    resolve: impl Fn(&str) -> Option<String>,
+) -> Vec<DocumentLink> {
+    templates
+        .into_iter()
+        .filter_map(|template| {
+            let target = Url::parse(&resolve(&template.name)?).ok()?;
+            Some(DocumentLink {
+                range: template_path_range(&template),
+                target: Some(target),
+                tooltip: Some(template.name),
+                data: None,
+            })
+        })
+        .collect()
+}
+
+/// Macros, blocks, `for` and `with` statements contain symbols that are
+/// defined in their body. Range of container covers whole body.
+pub fn nested_symbols(
+    tree: &Tree,
+    source: &str,
+    queries: &Queries,
+    variables: &[Identifier],
+) -> Option<Vec<DocumentSymbol>> {
+    let query = &queries.jinja_definitions;
+    let definitions = definition_query(query, tree, Point::default(), source, true);
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let statements: Vec<(Point, Point)> = root
+        .named_children(&mut cursor)
+        .filter(|node| node.kind() == "statement")
+        .map(|node| (node.start_position(), node.end_position()))
+        .collect();
+    let statement_at = |point: Point| {
+        statements
+            .iter()
+            .find(|statement| statement.0 <= point && point <= statement.1)
+            .copied()
+    };
+    #[allow(deprecated)]
+    let symbol = |name: String, kind, range, selection_range, detail| DocumentSymbol {
+        name,
+        detail,
+        kind,
+        range: to_range(range),
+        selection_range: to_range(selection_range),
+        children: None,
+        deprecated: None,
+        tags: None,
+    };
+
+    // (range, symbol, is container)
+    let mut nodes = vec![];
+    let mut containers = HashSet::new();
+    for scope in &definitions.scopes {
+        let (Some(opener), Some(closer)) = (statement_at(scope.start), statement_at(scope.end))
+        else {
+            continue;
+        };
+        let in_opener =
+            |variable: &&Identifier| opener.0 <= variable.start && variable.end <= opener.1;
+        let names: Vec<&Identifier> = match scope.keyword.as_str() {
+            "macro" => variables
+                .iter()
+                .filter(|variable| variable.identifier_type == IdentifierType::MacroName)
+                .filter(in_opener)
+                .take(1)
+                .collect(),
+            "block" => variables
+                .iter()
+                .filter(|variable| variable.identifier_type == IdentifierType::TemplateBlock)
+                .filter(in_opener)
+                .take(1)
+                .collect(),
+            "for" | "with" => variables
+                .iter()
+                .filter(|variable| variable.scope_ends.0 == scope.id)
+                .filter(|variable| {
+                    matches!(
+                        variable.identifier_type,
+                        IdentifierType::ForLoopKey
+                            | IdentifierType::ForLoopValue
+                            | IdentifierType::WithVariable
+                    )
+                })
+                .collect(),
+            _ => continue,
+        };
+        let Some(first) = names.first() else {
+            continue;
+        };
+        let name = names
+            .iter()
+            .map(|name| name.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let detail =
+            matches!(scope.keyword.as_str(), "for" | "with").then(|| scope.keyword.to_owned());
+        let range = (opener.0, closer.1);
+        let kind = first.identifier_type.symbol_kind();
+        nodes.push((
+            range,
+            symbol(name, kind, range, (first.start, first.end), detail),
+            true,
+        ));
+        for name in names {
+            containers.insert((name.start, name.end));
+        }
+    }
+    for variable in variables {
+        let location = (variable.start, variable.end);
+        if variable.identifier_type == IdentifierType::JinjaTemplate
+            || containers.contains(&location)
+        {
+            continue;
+        }
+        let kind = variable.identifier_type.symbol_kind();
+        let name = variable.name.to_owned();
+        nodes.push((
+            location,
+            symbol(name, kind, location, location, None),
+            false,
+        ));
+    }
+
+    nodes.sort_by(|a, b| a.0.0.cmp(&b.0.0).then(b.0.1.cmp(&a.0.1)));
+    let mut parents = vec![None; nodes.len()];
+    let mut stack: Vec<usize> = vec![];
+    for (index, (range, _, container)) in nodes.iter().enumerate() {
+        while let Some(top) = stack.last() {
+            let parent = nodes[*top].0;
+            if parent.0 <= range.0 && range.1 <= parent.1 {
+                break;
+            }
+            stack.pop();
+        }
+        parents[index] = stack.last().copied();
+        if *container {
+            stack.push(index);
+        }
+    }
+    let mut symbols: Vec<Option<DocumentSymbol>> =
+        nodes.into_iter().map(|node| Some(node.1)).collect();
+    let mut roots = vec![];
+    for index in (0..symbols.len()).rev() {
+        let symbol = symbols[index].take()?;
+        match parents[index] {
+            Some(parent) => symbols[parent]
+                .as_mut()?
+                .children
+                .get_or_insert_with(Vec::new)
+                .insert(0, symbol),
+            None => roots.insert(0, symbol),
+        }
+    }
+    Some(roots)
+}
+
+/// Folding ranges of closed scopes and of comments that span multiple lines.
+pub fn folding_ranges(tree: &Tree, source: &str, queries: &Queries) -> Vec<FoldingRange> {
+    let query = &queries.jinja_definitions;
+    let definitions = definition_query(query, tree, Point::default(), source, true);
+    let mut ranges = vec![];
+    for scope in definitions.scopes {
+        // Closing tag stays visible, so range ends on line before it.
+        if scope.id == 0 || scope.end.row <= scope.start.row + 1 {
+            continue;
+        }
+        ranges.push(FoldingRange {
+            start_line: scope.start.row as u32,
+            end_line: scope.end.row as u32 - 1,
+            kind: Some(FoldingRangeKind::Region),
+            ..Default::default()
+        });
+    }
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    for node in root.named_children(&mut cursor) {
+        let start = node.start_position();
+        let end = node.end_position();
+        if node.kind() != "comment" || end.row == start.row {
+            continue;
+        }
+        ranges.push(FoldingRange {
+            start_line: start.row as u32,
+            end_line: end.row as u32,
+            kind: Some(FoldingRangeKind::Comment),
+            ..Default::default()
+        });
+    }
+    ranges.sort_by_key(|range| (range.start_line, range.end_line));
+    ranges
+}
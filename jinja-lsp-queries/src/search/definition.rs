@@ -3,7 +3,7 @@ use std::collections::{HashMap, HashSet, LinkedList};
 use tree_sitter::{Point, Query, QueryCapture, QueryCursor, StreamingIterator, Tree};
 
 use crate::{
-    search::{Identifier, IdentifierType},
+    search::{Identifier, IdentifierType, signature::parameter_names},
     tree_builder::JinjaDiagnostic,
 };
 
@@ -24,6 +24,14 @@ impl Scope {
             id: 0,
         }
     }
+
+    /// Keyword that closes this scope.
+    pub fn end_keyword(&self) -> String {
+        match self.keyword.as_str() {
+            "elif" | "else" => String::from("endif"),
+            keyword => format!("end{keyword}"),
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
@@ -31,6 +39,10 @@ pub enum ScopeError {
     WrongEndScopeKeyword(Scope),
     ElifStatement(Scope),
     ElseStatement(Scope),
+    /// Scope that is still open at the end of file, located at opening keyword.
+    Unclosed(Scope),
+    /// End keyword without opening statement.
+    UnexpectedEnd(Scope),
 }
 
 impl ScopeError {
@@ -49,6 +61,10 @@ impl ScopeError {
                 identifier.start = scope.start;
                 identifier.end = scope.end;
             }
+            ScopeError::Unclosed(scope) | ScopeError::UnexpectedEnd(scope) => {
+                identifier.start = scope.start;
+                identifier.end = scope.end;
+            }
         }
 
         (JinjaDiagnostic::ScopeError(self.clone()), identifier)
@@ -61,6 +77,11 @@ pub struct JinjaDefinitions {
     pub definitions: HashMap<usize, HashMap<usize, Identifier>>,
     pub statements: HashSet<usize>,
     pub errors: Vec<ScopeError>,
+    pub block_ends: Vec<Identifier>,
+    /// Scopes that are already closed, in order in which they were closed.
+    pub scopes: Vec<Scope>,
+    /// Start of `if` scope for each `elif` and `else` scope, by scope id.
+    if_starts: HashMap<usize, Point>,
     scope_id: usize,
     last_keyword: String,
     keyword_location: (Point, Point),
@@ -115,6 +136,14 @@ impl JinjaDefinitions {
             }
             "macro_parameter" => {
                 let node_id = capture.node.id();
+                // identifiers of default values are captured too
+                let statement = capture.node.parent()?;
+                let is_parameter = parameter_names(statement, source)
+                    .iter()
+                    .any(|name| name.id() == node_id);
+                if !is_parameter {
+                    return Some(true);
+                }
                 let scope = self.current_scope.front()?;
                 let definitions = self.definitions.get_mut(&scope.id)?;
                 if definitions.contains_key(&node_id) {
@@ -254,23 +283,45 @@ impl JinjaDefinitions {
                         end_if_scope = true;
                     }
                 }
+                let mut if_start = None;
                 if end_if_scope {
                     let mut scope = self.current_scope.pop_front()?;
                     scope.end = capture.node.start_position();
+                    self.scopes.push(scope.clone());
+                    if_start = Some(
+                        self.if_starts
+                            .get(&scope.id)
+                            .copied()
+                            .unwrap_or(scope.start),
+                    );
                     let definitions = self.definitions.get_mut(&scope.id)?;
                     for definition in definitions {
                         definition.1.scope_ends.1 = scope.end;
                     }
                 }
                 self.new_scope(self.last_keyword.to_string(), capture.node.end_position());
+                if let Some(if_start) = if_start {
+                    self.if_starts.insert(self.scope_id, if_start);
+                }
             }
             "statement_end" => {
                 if self.statements.contains(&capture.node.id()) {
                     return Some(true);
                 }
                 self.statements.insert(capture.node.id());
+                if self.current_scope.front().is_none_or(|scope| scope.id == 0) {
+                    let scope = Scope {
+                        id: 0,
+                        keyword: self.last_keyword.to_string(),
+                        start: self.keyword_location.0,
+                        end: self.keyword_location.1,
+                    };
+                    self.errors.push(ScopeError::UnexpectedEnd(scope));
+                    return Some(true);
+                }
                 let mut scope = self.current_scope.pop_front()?;
                 scope.end = capture.node.start_position();
+                self.scopes.push(scope.clone());
                 if !self.last_keyword.ends_with(&scope.keyword) {
                     let is_error = {
                         if self.last_keyword == "endif" {
@@ -308,6 +359,16 @@ impl JinjaDefinitions {
                 identifier.identifier_type = IdentifierType::TemplateBlock;
                 definitions.insert(capture.node.id(), identifier);
             }
+            "endblock_variable" => {
+                let name = capture.node.utf8_text(source.as_bytes()).unwrap();
+                let mut identifier = Identifier::new(
+                    name,
+                    capture.node.start_position(),
+                    capture.node.end_position(),
+                );
+                identifier.identifier_type = IdentifierType::TemplateBlock;
+                self.block_ends.push(identifier);
+            }
 
             _ => {}
         }
@@ -354,6 +415,30 @@ pub fn definition_query(
             }
         }
     }
+    let end = tree.root_node().end_position();
+    while let Some(scope) = definitions.current_scope.front()
+        && scope.id != 0
+    {
+        let Some(scope) = definitions.current_scope.pop_front() else {
+            break;
+        };
+        if let Some(definitions) = definitions.definitions.get_mut(&scope.id) {
+            for definition in definitions.values_mut() {
+                definition.scope_ends.1 = end;
+            }
+        }
+        // unclosed `else` is reported at its `if`
+        let opening = match definitions.if_starts.get(&scope.id) {
+            Some(start) => Scope {
+                keyword: String::from("if"),
+                start: *start,
+                ..scope
+            },
+            None => scope,
+        };
+        let opening = opening_keyword(tree, &opening);
+        definitions.errors.push(ScopeError::Unclosed(opening));
+    }
     if let Some(scope) = definitions.current_scope.front() {
         if let Some(definitions) = definitions.definitions.get_mut(&0) {
             for (_, id) in definitions {
@@ -363,3 +448,27 @@ pub fn definition_query(
     }
     definitions
 }
+
+/// Copy of `scope` located at keyword of its opening statement.
+fn opening_keyword(tree: &Tree, scope: &Scope) -> Scope {
+    let mut opening = scope.clone();
+    opening.end = scope.start;
+    // scope starts at the end of opening statement or macro name
+    let point = Point::new(scope.start.row, scope.start.column.saturating_sub(1));
+    let mut node = tree.root_node().descendant_for_point_range(point, point);
+    while let Some(current) = node {
+        if current.kind() == "statement" {
+            let mut cursor = current.walk();
+            let keyword = current
+                .named_children(&mut cursor)
+                .find(|child| child.kind() == "keyword");
+            if let Some(keyword) = keyword {
+                opening.start = keyword.start_position();
+                opening.end = keyword.end_position();
+            }
+            break;
+        }
+        node = current.parent();
+    }
+    opening
+}
@@ -1,4 +1,4 @@
-use tower_lsp::lsp_types::{CompletionItemKind, Position, Range, SymbolKind};
+use tower_lsp::lsp_types::{CompletionItemKind, Position, Range, SemanticTokenType, SymbolKind};
 use tree_sitter::Point;
 
 use crate::search::objects::JinjaObject;
@@ -7,9 +7,15 @@ pub mod definition;
 pub mod objects;
 pub mod python_identifiers;
 pub mod queries;
+pub mod render_context;
+pub mod resolve;
 pub mod rust_identifiers;
 pub mod rust_template_completion;
+pub mod semantic_tokens;
+pub mod signature;
 pub mod snippets_completion;
+pub mod syntax;
+pub mod template_graph;
 pub mod templates;
 pub mod test_queries;
 
@@ -66,6 +72,28 @@ pub fn completion_start(trigger_point: Point, identifier: &Identifier) -> Option
     let s = identifier.name.get(0..to);
     s
 }
+
+/// Fuzzy match where every character of `query` has to appear in `candidate`
+/// in the same order. Lower score is better match, `None` means no match.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    for (index, c) in query.to_lowercase().chars().enumerate() {
+        let found = candidate
+            .iter()
+            .skip(position)
+            .position(|item| *item == c)?;
+        if index == 0 {
+            score += found;
+        } else {
+            score += found * 2;
+        }
+        position += found + 1;
+    }
+    Some(score + candidate.len() - position)
+}
+
 pub fn to_range(points: (Point, Point)) -> Range {
     let start = Position::new(points.0.row as u32, points.0.column as u32);
     let end = Position::new(points.1.row as u32, points.1.column as u32);
@@ -146,4 +174,20 @@ impl IdentifierType {
             IdentifierType::JinjaTemplate => SymbolKind::FILE,
         }
     }
+
+    pub fn semantic_token_type(&self) -> SemanticTokenType {
+        match self {
+            IdentifierType::ForLoopKey => SemanticTokenType::VARIABLE,
+            IdentifierType::ForLoopValue => SemanticTokenType::VARIABLE,
+            IdentifierType::ForLoopCount => SemanticTokenType::VARIABLE,
+            IdentifierType::SetVariable => SemanticTokenType::VARIABLE,
+            IdentifierType::WithVariable => SemanticTokenType::VARIABLE,
+            IdentifierType::MacroName => SemanticTokenType::MACRO,
+            IdentifierType::MacroParameter => SemanticTokenType::PARAMETER,
+            IdentifierType::TemplateBlock => SemanticTokenType::NAMESPACE,
+            IdentifierType::BackendVariable => SemanticTokenType::VARIABLE,
+            IdentifierType::UndefinedVariable => SemanticTokenType::VARIABLE,
+            IdentifierType::JinjaTemplate => SemanticTokenType::STRING,
+        }
+    }
 }
@@ -74,7 +74,6 @@ pub struct JinjaObjects {
     pub objects: Vec<JinjaObject>,
     pub previous_node_id: HashSet<usize>,
     pub previous_nodes: Vec<(CompletionMember, (Point, Point))>,
-    test: bool,
 }
 
 impl JinjaObjects {
@@ -118,8 +117,8 @@ impl JinjaObjects {
                 if VALID_IDENTIFIERS.contains(&value) {
                     return Some(());
                 }
-                let is_test = self.test;
-                let is_filter = self.is_filter(None);
+                let is_test = self.is_test();
+                let is_filter = self.is_filter();
                 let mut object = JinjaObject::new(String::from(value), is_filter, is_test);
                 object.fields.push((String::from(value), (start, end)));
                 self.objects.push(object);
@@ -252,18 +251,21 @@ impl JinjaObjects {
         trigger_point >= full_range.0 && trigger_point <= full_range.1
     }
 
-    pub fn is_filter(&self, trigger_point: Option<Point>) -> bool {
-        for (member, (start, end)) in &self.previous_nodes {
-            if let CompletionMember::FilterOperator = member {
-                if let Some(trigger_point) = trigger_point {
-                    if &trigger_point >= start && &trigger_point <= end {
-                        return true;
-                    }
-                }
-                return true;
-            }
-        }
-        return false;
+    /// Identifier that comes right after filter operator, filter arguments
+    /// are not filters.
+    fn is_filter(&self) -> bool {
+        matches!(
+            self.previous_nodes.last(),
+            Some((CompletionMember::FilterOperator, _))
+        )
+    }
+
+    /// Identifier that comes right after `is` keyword.
+    fn is_test(&self) -> bool {
+        matches!(
+            self.previous_nodes.last(),
+            Some((CompletionMember::Test, _))
+        )
     }
 
     pub fn is_ident(&self, trigger_point: Point) -> Option<String> {
@@ -94,5 +94,13 @@
   (#eq? @block "block")
 )
 
+(statement
+  (statement_begin)
+  (keyword) @endblock
+  .
+  (identifier) @endblock_variable
+  (#eq? @endblock "endblock")
+)
+
 (ERROR) @error
 
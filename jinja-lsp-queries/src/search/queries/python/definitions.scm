@@ -3,7 +3,7 @@
     (attribute
       object: (identifier)* @object
       attribute: (identifier) @field
-      (#match? @field "^(globals|filters)$")
+      (#match? @field "^(globals|filters|tests)$")
       (#eq? @object "jinja_env")
     )
       (string
@@ -72,6 +72,22 @@
       )
   )
 )
+
+(call
+	function: (attribute
+    	attribute: (identifier) @star_api
+        (#eq? @star_api "TemplateResponse")
+    )
+  arguments: (argument_list
+  	(dictionary
+    	(pair
+        	key: (string
+            	(string_content) @key_id
+            )
+        )
+    )
+  )
+)
   
   (ERROR) @error
 
@@ -28,8 +28,8 @@
 
 (call
 	function: (attribute
-    	attribute: (identifier) @star_api
-        (#eq? @star_api "TemplateResponse")
+    	attribute: (identifier) @method_name
+        (#eq? @method_name "TemplateResponse")
     )
   arguments: (argument_list
   	(_)
@@ -11,15 +11,14 @@
     
     (
     	(field_expression
-        	(identifier) @jinja
             (field_identifier) @method
         )
         (arguments
-        	(string_literal)+ @name
+        	.
+        	(string_literal) @name
         )
     
-        (#eq? @jinja "jinja")
-        (#match? @method "(add_global|add_filter|add_function)")
+        (#match? @method "^(add_global|add_filter|add_function|add_test)$")
     
     ) @function
 
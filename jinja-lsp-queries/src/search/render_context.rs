@@ -0,0 +1,459 @@
+use std::collections::HashSet;
+
+use tree_sitter::{Node, Point, Tree};
+
+use super::{Identifier, IdentifierType};
+
+/// Backend call that renders template, with variables passed to it.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct RenderCall {
+    pub template: Identifier,
+    pub context: Vec<Identifier>,
+    /// Context is also passed with `**kwargs`, `..spread`, variable or other
+    /// expression, so it can have keys that aren't in `context`.
+    pub dynamic: bool,
+    /// Call that passes context to template, `None` when template is loaded
+    /// and rendered somewhere else.
+    pub site: Option<(Point, Point)>,
+}
+
+/// Variables of backend file split between render calls and globals.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct BackendContext {
+    pub calls: Vec<RenderCall>,
+    /// Registered on environment, or passed to call that isn't linked to any
+    /// template.
+    pub globals: Vec<Identifier>,
+    /// Filters registered on environment.
+    pub filters: Vec<Identifier>,
+    /// Tests registered on environment.
+    pub tests: Vec<Identifier>,
+}
+
+/// Links context keys found by `backend_definition_query` to templates found
+/// by `backend_templates_query`. Key belongs to template from the same
+/// statement, or to the closest template before it in the same function.
+pub fn render_context(tree: &Tree, source: &str, variables: &[Identifier]) -> BackendContext {
+    let root = tree.root_node();
+    let templates: Vec<&Identifier> = variables
+        .iter()
+        .filter(|variable| variable.identifier_type == IdentifierType::JinjaTemplate)
+        .collect();
+    let mut context = BackendContext {
+        calls: templates
+            .iter()
+            .map(|template| RenderCall {
+                template: (*template).to_owned(),
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    };
+    for variable in variables {
+        if variable.identifier_type != IdentifierType::BackendVariable {
+            continue;
+        }
+        let Some(node) = root.named_descendant_for_point_range(variable.start, variable.end) else {
+            context.globals.push(variable.to_owned());
+            continue;
+        };
+        let owner = match context_owner(node, source) {
+            Owner::Call(owner) => owner,
+            Owner::Global => {
+                context.globals.push(variable.to_owned());
+                continue;
+            }
+            Owner::Filter => {
+                context.filters.push(variable.to_owned());
+                continue;
+            }
+            Owner::Test => {
+                context.tests.push(variable.to_owned());
+                continue;
+            }
+        };
+        match owner_template(owner, &templates) {
+            Some(index) => context.calls[index].context.push(variable.to_owned()),
+            None => context.globals.push(variable.to_owned()),
+        }
+    }
+    for (index, site, dynamic) in render_sites(root, source, &templates) {
+        let call = &mut context.calls[index];
+        call.dynamic |= dynamic;
+        call.site
+            .get_or_insert((site.start_position(), site.end_position()));
+    }
+    for call in &mut context.calls {
+        // template from `get_template` that is rendered in other function
+        if call.site.is_none() {
+            call.dynamic = true;
+        }
+    }
+    context
+}
+
+/// Template from the same statement as `owner`, or the closest template
+/// before it in the same function.
+fn owner_template(owner: Node, templates: &[&Identifier]) -> Option<usize> {
+    let statement = enclosing(owner, |node| {
+        node.parent()
+            .is_some_and(|parent| matches!(parent.kind(), "block" | "module" | "source_file"))
+    });
+    let function = enclosing(owner, |node| {
+        matches!(node.kind(), "function_item" | "function_definition")
+    });
+    let start = owner.start_position();
+    let within = |node: Option<Node>, template: &Identifier| {
+        node.is_some_and(|node| {
+            node.start_position() <= template.start && template.end <= node.end_position()
+        })
+    };
+    let in_statement = templates
+        .iter()
+        .enumerate()
+        .filter(|(_, template)| within(statement, template))
+        .min_by_key(|(_, template)| (template.start > start, template.start));
+    let before = templates
+        .iter()
+        .enumerate()
+        .filter(|(_, template)| template.end <= start)
+        .filter(|(_, template)| function.is_none() || within(function, template))
+        .max_by_key(|(_, template)| template.start);
+    in_statement.or(before).map(|(index, _)| index)
+}
+
+/// Calls that pass context to templates: calls with template argument (other
+/// than `get_template`) and `render` methods. Each with index of template and
+/// whether context can't be read from literal arguments.
+fn render_sites<'a>(
+    root: Node<'a>,
+    source: &str,
+    templates: &[&Identifier],
+) -> Vec<(usize, Node<'a>, bool)> {
+    let mut sites = vec![];
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        let mut cursor = node.walk();
+        stack.extend(node.named_children(&mut cursor));
+        if !matches!(node.kind(), "call" | "call_expression") {
+            continue;
+        }
+        let Some(arguments) = node.child_by_field_name("arguments") else {
+            continue;
+        };
+        let mut cursor = arguments.walk();
+        let arguments: Vec<Node> = arguments
+            .named_children(&mut cursor)
+            .filter(|argument| !argument.kind().contains("comment"))
+            .collect();
+        let name = function_name(node, source);
+        let position = arguments.iter().position(|argument| {
+            templates.iter().any(|template| {
+                argument.start_position() <= template.start
+                    && template.end <= argument.end_position()
+            })
+        });
+        let (index, passed) = match position {
+            Some(_) if LOADER_METHODS.contains(&name) => continue,
+            Some(position) => {
+                let Some(index) = templates.iter().position(|template| {
+                    let argument = arguments[position];
+                    argument.start_position() <= template.start
+                        && template.end <= argument.end_position()
+                }) else {
+                    continue;
+                };
+                (index, &arguments[position + 1..])
+            }
+            None if RENDER_METHODS.contains(&name) => {
+                let Some(index) = owner_template(node, templates) else {
+                    continue;
+                };
+                // minijinja takes context as first argument
+                let passed = match node.kind() {
+                    "call_expression" => &arguments[..arguments.len().min(1)],
+                    _ => &arguments[..],
+                };
+                (index, passed)
+            }
+            None => continue,
+        };
+        let dynamic = passed
+            .iter()
+            .any(|argument| is_dynamic_context(*argument, source));
+        sites.push((index, node, dynamic));
+    }
+    sites.sort_by_key(|(_, site, _)| site.start_position());
+    sites
+}
+
+/// Argument that passes context which can't be read from source.
+fn is_dynamic_context(argument: Node, source: &str) -> bool {
+    let has_spread = |node: Node| {
+        let mut cursor = node.walk();
+        node.children(&mut cursor)
+            .any(|child| matches!(child.kind(), ".." | "dictionary_splat"))
+    };
+    match argument.kind() {
+        "macro_invocation" => {
+            let is_context = argument
+                .child_by_field_name("macro")
+                .and_then(|name| name.utf8_text(source.as_bytes()).ok())
+                == Some("context");
+            let mut cursor = argument.walk();
+            let tokens = argument
+                .named_children(&mut cursor)
+                .find(|child| child.kind() == "token_tree");
+            !is_context || tokens.is_none_or(has_spread)
+        }
+        "keyword_argument" => {
+            let name = argument
+                .child_by_field_name("name")
+                .and_then(|name| name.utf8_text(source.as_bytes()).ok());
+            let value = argument.child_by_field_name("value");
+            name == Some("context")
+                && value.is_none_or(|value| value.kind() != "dictionary" || has_spread(value))
+        }
+        "dictionary" => has_spread(argument),
+        "unit_expression" | "string" | "string_literal" => false,
+        _ => true,
+    }
+}
+
+/// Last part of function or method name of `call`.
+fn function_name<'a>(call: Node, source: &'a str) -> &'a str {
+    let Some(function) = call.child_by_field_name("function") else {
+        return "";
+    };
+    let method = function
+        .child_by_field_name("attribute")
+        .or_else(|| function.child_by_field_name("field"))
+        .or_else(|| function.child_by_field_name("name"))
+        .unwrap_or(function);
+    method.utf8_text(source.as_bytes()).unwrap_or_default()
+}
+
+/// Range to replace and text that passes `names` to template of `call`,
+/// written in syntax of that call. Text goes after the last context key, into
+/// empty context or after the last argument.
+pub fn context_insertion(
+    tree: &Tree,
+    source: &str,
+    call: &RenderCall,
+    names: &[String],
+) -> Option<((Point, Point), String)> {
+    let root = tree.root_node();
+    if let Some(key) = call.context.iter().max_by_key(|key| key.start) {
+        let node = root.named_descendant_for_point_range(key.start, key.end)?;
+        let container = enclosing(node, |node| {
+            matches!(node.kind(), "token_tree" | "argument_list" | "dictionary")
+        })?;
+        let last = container.child(container.child_count().checked_sub(2)?)?;
+        let entries = context_entries(container.kind(), names);
+        let text = if last.kind() == "," {
+            format!(" {entries},")
+        } else {
+            format!(", {entries}")
+        };
+        let position = last.end_position();
+        return Some(((position, position), text));
+    }
+    let (start, end) = call.site?;
+    let site = enclosing(root.descendant_for_point_range(start, end)?, |node| {
+        matches!(node.kind(), "call" | "call_expression")
+    })?;
+    let list = site.child_by_field_name("arguments")?;
+    let mut cursor = list.walk();
+    let arguments: Vec<Node> = list
+        .named_children(&mut cursor)
+        .filter(|argument| !argument.kind().contains("comment"))
+        .collect();
+    let container = arguments.iter().find_map(|argument| match argument.kind() {
+        "macro_invocation" => {
+            let mut cursor = argument.walk();
+            argument
+                .named_children(&mut cursor)
+                .find(|child| child.kind() == "token_tree")
+        }
+        "dictionary" => Some(*argument),
+        "keyword_argument" => argument
+            .child_by_field_name("value")
+            .filter(|value| value.kind() == "dictionary"),
+        _ => None,
+    });
+    if let Some(container) = container {
+        let position = container.child(0)?.end_position();
+        let entries = context_entries(container.kind(), names);
+        let text = match container.kind() {
+            "token_tree" => format!(" {entries} "),
+            _ => entries,
+        };
+        return Some(((position, position), text));
+    }
+    let macro_text = format!("context! {{ {} }}", context_entries("token_tree", names));
+    let unit = arguments
+        .iter()
+        .find(|argument| argument.kind() == "unit_expression");
+    if let Some(unit) = unit {
+        return Some(((unit.start_position(), unit.end_position()), macro_text));
+    }
+    let text = match site.kind() {
+        "call_expression" => macro_text,
+        _ if function_name(site, source) == "TemplateResponse" => {
+            format!("context={{{}}}", context_entries("dictionary", names))
+        }
+        _ => context_entries("argument_list", names),
+    };
+    let (position, text) = match arguments.last() {
+        Some(last) => (last.end_position(), format!(", {text}")),
+        None => (list.child(0)?.end_position(), text),
+    };
+    Some(((position, position), text))
+}
+
+/// Context entries that pass `names`, in syntax of `container` kind.
+fn context_entries(container: &str, names: &[String]) -> String {
+    let entries: Vec<String> = names
+        .iter()
+        .map(|name| match container {
+            "token_tree" => format!("{name} => ()"),
+            "argument_list" => format!("{name}=None"),
+            _ => format!("\"{name}\": None"),
+        })
+        .collect();
+    entries.join(", ")
+}
+
+/// Names from `used` that rendered templates read, but neither `call` nor
+/// `globals` define.
+pub fn missing_names(call: &RenderCall, used: &[String], globals: &HashSet<String>) -> Vec<String> {
+    used.iter()
+        .filter(|name| !globals.contains(*name))
+        .filter(|name| !call.context.iter().any(|key| &key.name == *name))
+        .cloned()
+        .collect()
+}
+
+/// Context keys of `call` that aren't in `used` names of rendered templates.
+/// None for dynamic context, its keys can be used by template through it.
+pub fn unused_keys<'a>(call: &'a RenderCall, used: &[String]) -> Vec<&'a Identifier> {
+    if call.dynamic {
+        return vec![];
+    }
+    call.context
+        .iter()
+        .filter(|key| !used.contains(&key.name))
+        .collect()
+}
+
+/// Range of context entry with `key` and of comma that separates it from
+/// other entries, removing it leaves valid context.
+pub fn context_removal(tree: &Tree, key: &Identifier) -> Option<(Point, Point)> {
+    let node = tree
+        .root_node()
+        .named_descendant_for_point_range(key.start, key.end)?;
+    let container = enclosing(node, |node| {
+        matches!(node.kind(), "token_tree" | "argument_list" | "dictionary")
+    })?;
+    let mut cursor = container.walk();
+    let children: Vec<Node> = container.children(&mut cursor).collect();
+    // entries between delimiters, split by commas
+    let mut entries: Vec<Vec<Node>> = vec![vec![]];
+    for child in children.get(1..children.len().checked_sub(1)?)? {
+        if child.kind() == "," {
+            entries.push(vec![]);
+        } else if let Some(entry) = entries.last_mut() {
+            entry.push(*child);
+        }
+    }
+    entries.retain(|entry| !entry.is_empty());
+    let index = entries.iter().position(|entry| {
+        entry
+            .first()
+            .is_some_and(|first| first.start_position() <= key.start)
+            && entry
+                .last()
+                .is_some_and(|last| key.end <= last.end_position())
+    })?;
+    let entry = &entries[index];
+    let start = entry.first()?.start_position();
+    let end = entry.last()?.end_position();
+    if let Some(next) = entries.get(index + 1) {
+        Some((start, next.first()?.start_position()))
+    } else if let Some(previous) = index.checked_sub(1).and_then(|index| entries.get(index)) {
+        Some((previous.last()?.end_position(), end))
+    } else {
+        let comma = entry
+            .last()?
+            .next_sibling()
+            .filter(|next| next.kind() == ",");
+        Some((start, comma.map_or(end, |comma| comma.end_position())))
+    }
+}
+
+/// Where backend variable is defined.
+enum Owner<'a> {
+    /// `context!` macro or call with keyword arguments.
+    Call(Node<'a>),
+    Global,
+    Filter,
+    Test,
+}
+
+fn context_owner<'a>(node: Node<'a>, source: &str) -> Owner<'a> {
+    let mut current = Some(node);
+    while let Some(node) = current {
+        match node.kind() {
+            "subscript" => {
+                // jinja_env.globals["name"]
+                let registry = node
+                    .child_by_field_name("value")
+                    .and_then(|value| value.child_by_field_name("attribute"))
+                    .and_then(|registry| registry.utf8_text(source.as_bytes()).ok());
+                return registered(registry.unwrap_or_default());
+            }
+            "macro_invocation" => return Owner::Call(node),
+            "call" | "call_expression" => {
+                let name = function_name(node, source);
+                if node.child_by_field_name("function").is_none() {
+                    return Owner::Global;
+                }
+                if GLOBAL_METHODS.contains(&name) {
+                    return registered(name);
+                }
+                return Owner::Call(node);
+            }
+            _ => current = node.parent(),
+        }
+    }
+    Owner::Global
+}
+
+/// Kind of registration from method or attribute name.
+fn registered<'a>(name: &str) -> Owner<'a> {
+    match name {
+        "add_filter" | "filters" => Owner::Filter,
+        "add_test" | "tests" => Owner::Test,
+        _ => Owner::Global,
+    }
+}
+
+/// First ancestor (or node itself) that matches `predicate`.
+fn enclosing<'a>(node: Node<'a>, predicate: impl Fn(&Node<'a>) -> bool) -> Option<Node<'a>> {
+    let mut current = Some(node);
+    while let Some(node) = current {
+        if predicate(&node) {
+            return Some(node);
+        }
+        current = node.parent();
+    }
+    None
+}
+
+static GLOBAL_METHODS: [&str; 4] = ["add_global", "add_function", "add_filter", "add_test"];
+
+/// Calls that load template without rendering it.
+static LOADER_METHODS: [&str; 1] = ["get_template"];
+
+/// Methods of loaded template that render it with context.
+static RENDER_METHODS: [&str; 3] = ["render", "render_async", "render_to_write"];
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+
+use tree_sitter::{Node, Point, Tree};
+
+use super::{
+    Identifier, IdentifierType, definition::Scope, objects::JinjaObject, templates::Import,
+};
+
+/// Definition that `name` at `point` refers to. When definitions shadow each
+/// other the one that starts last wins, same as in goto definition.
+pub fn resolve_definition<'a>(
+    variables: &'a [Identifier],
+    name: &str,
+    point: Point,
+) -> Option<&'a Identifier> {
+    variables
+        .iter()
+        .filter(|variable| {
+            variable.name == name
+                && variable.identifier_type != IdentifierType::TemplateBlock
+                && variable.identifier_type != IdentifierType::JinjaTemplate
+        })
+        .filter(|variable| variable.start <= point && point <= variable.scope_ends.1)
+        .max()
+}
+
+/// Locations of `definition` and of every object that resolves to it.
+pub fn definition_occurrences(
+    objects: &[JinjaObject],
+    variables: &[Identifier],
+    definition: &Identifier,
+) -> Vec<(Point, Point)> {
+    let mut occurrences = vec![(definition.start, definition.end)];
+    for object in objects {
+        if object.is_filter || object.is_test || object.name != definition.name {
+            continue;
+        }
+        let Some((_, location)) = object.fields.first() else {
+            continue;
+        };
+        let resolved = resolve_definition(variables, &object.name, location.0);
+        if resolved == Some(definition) && !occurrences.contains(location) {
+            occurrences.push(*location);
+        }
+    }
+    occurrences
+}
+
+/// Locations of objects named `name` that are not defined in this template,
+/// for example macros brought in with `{% from ... import name %}`.
+pub fn unresolved_occurrences(
+    objects: &[JinjaObject],
+    variables: &[Identifier],
+    name: &str,
+) -> Vec<(Point, Point)> {
+    let mut occurrences = vec![];
+    for object in objects {
+        if object.is_filter || object.is_test || object.name != name {
+            continue;
+        }
+        let Some((_, location)) = object.fields.first() else {
+            continue;
+        };
+        let resolved = resolve_definition(variables, name, location.0);
+        if resolved.is_none() && !occurrences.contains(location) {
+            occurrences.push(*location);
+        }
+    }
+    occurrences
+}
+
+/// Locations of `module.name` attribute accesses, where `module` comes from
+/// `{% import ... as module %}`.
+pub fn module_occurrences(
+    objects: &[JinjaObject],
+    module: &str,
+    name: &str,
+) -> Vec<(Point, Point)> {
+    let mut occurrences = vec![];
+    for object in objects {
+        let mut fields = object.fields.iter();
+        let (Some(first), Some(second)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        if first.0 == module && second.0 == name && !occurrences.contains(&second.1) {
+            occurrences.push(second.1);
+        }
+    }
+    occurrences
+}
+
+pub fn is_valid_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Statements that belong to the same block as `statement`, for example
+/// `if`, `elif`, `else` and `endif`. Statements are connected through closed
+/// scopes, scope starts in one statement and ends in the next one.
+pub fn matching_statements(
+    scopes: &[Scope],
+    statements: &[(Point, Point)],
+    statement: (Point, Point),
+) -> Vec<(Point, Point)> {
+    let within = |point: Point, range: &(Point, Point)| range.0 <= point && point <= range.1;
+    let containing = |point: Point| statements.iter().find(|range| within(point, range));
+    let mut matching = vec![statement];
+    let mut current = statement;
+    while let Some(next) = scopes
+        .iter()
+        .find(|scope| scope.id != 0 && within(scope.start, &current))
+        .and_then(|scope| containing(scope.end))
+    {
+        if matching.contains(next) {
+            break;
+        }
+        matching.push(*next);
+        current = *next;
+    }
+    current = statement;
+    while let Some(previous) = scopes
+        .iter()
+        .find(|scope| scope.id != 0 && within(scope.end, &current))
+        .and_then(|scope| containing(scope.start))
+    {
+        if matching.contains(previous) {
+            break;
+        }
+        matching.push(*previous);
+        current = *previous;
+    }
+    matching.sort();
+    matching
+}
+
+/// Statements with their first keyword.
+pub fn statement_keywords(root: Node) -> HashMap<(Point, Point), Node> {
+    let mut keywords = HashMap::new();
+    let mut cursor = root.walk();
+    for node in root.named_children(&mut cursor) {
+        if node.kind() != "statement" {
+            continue;
+        }
+        let mut cursor = node.walk();
+        let keyword = node
+            .named_children(&mut cursor)
+            .find(|child| child.kind() == "keyword");
+        if let Some(keyword) = keyword {
+            keywords.insert((node.start_position(), node.end_position()), keyword);
+        }
+    }
+    keywords
+}
+
+/// Keyword of opening tag with keyword of end tag (without `end`), or block
+/// name with name repeated in `{% endblock name %}`, when `point` is on one
+/// of them.
+pub fn linked_ranges(
+    tree: &Tree,
+    source: &str,
+    scopes: &[Scope],
+    point: Point,
+) -> Option<Vec<(Point, Point)>> {
+    let keywords = statement_keywords(tree.root_node());
+    let within = |node: Node| node.start_position() <= point && point <= node.end_position();
+    let statement = keywords
+        .iter()
+        .find(|(statement, _)| statement.0 <= point && point <= statement.1)
+        .map(|(statement, _)| *statement)?;
+    let statements: Vec<(Point, Point)> = keywords.keys().copied().collect();
+    let matching = matching_statements(scopes, &statements, statement);
+    let opener = *keywords.get(matching.first()?)?;
+    let closer = *keywords.get(matching.last()?)?;
+    let opener_text = opener.utf8_text(source.as_bytes()).ok()?;
+    let closer_text = closer.utf8_text(source.as_bytes()).ok()?;
+    if matching.len() < 2 || closer_text.strip_prefix("end") != Some(opener_text) {
+        return None;
+    }
+    if within(opener) || within(closer) {
+        let mut end_keyword = closer.start_position();
+        end_keyword.column += 3;
+        if within(closer) && point < end_keyword {
+            return None;
+        }
+        return Some(vec![
+            (opener.start_position(), opener.end_position()),
+            (end_keyword, closer.end_position()),
+        ]);
+    }
+    let opener_name = opener.next_named_sibling()?;
+    let closer_name = closer.next_named_sibling()?;
+    let names = [opener_name, closer_name];
+    if opener_text != "block"
+        || names.iter().any(|name| name.kind() != "identifier")
+        || !names.iter().any(|name| within(*name))
+        || opener_name.utf8_text(source.as_bytes()) != closer_name.utf8_text(source.as_bytes())
+    {
+        return None;
+    }
+    Some(
+        names
+            .iter()
+            .map(|name| (name.start_position(), name.end_position()))
+            .collect(),
+    )
+}
+
+/// Object with field under the cursor and index of that field.
+pub fn object_at(objects: &[JinjaObject], point: Point) -> Option<(&JinjaObject, usize)> {
+    objects.iter().find_map(|object| {
+        let index = object
+            .fields
+            .iter()
+            .position(|field| field.1.0 <= point && point <= field.1.1)?;
+        Some((object, index))
+    })
+}
+
+/// Name of macro called at `point`, `name` or `module.name`. `None` when
+/// object is defined in this template as something other than macro.
+pub fn macro_callee(
+    objects: &[JinjaObject],
+    variables: &[Identifier],
+    point: Point,
+) -> Option<String> {
+    let (object, index) = object_at(objects, point)?;
+    if object.is_filter || object.is_test {
+        return None;
+    }
+    match index {
+        0 => {
+            let (name, location) = object.fields.first()?;
+            let local = resolve_definition(variables, name, location.0);
+            if local.is_some_and(|local| local.identifier_type != IdentifierType::MacroName) {
+                return None;
+            }
+            Some(name.to_owned())
+        }
+        1 => Some(format!(
+            "{}.{}",
+            object.fields.first()?.0,
+            object.fields.get(1)?.0
+        )),
+        _ => None,
+    }
+}
+
+/// Template and macro name from `{% from %}` statement, when `point` is on
+/// imported name or on its alias.
+pub fn imported_name_at(imports: &[Import], point: Point) -> Option<(&Identifier, &Identifier)> {
+    imports.iter().find_map(|import| {
+        let Import::From { template, .. } = import else {
+            return None;
+        };
+        import
+            .imported_names()
+            .into_iter()
+            .find_map(|(name, alias)| {
+                let on_name = name.start <= point && point <= name.end;
+                let on_alias =
+                    alias.is_some_and(|alias| alias.start <= point && point <= alias.end);
+                (on_name || on_alias).then_some((template, name))
+            })
+    })
+}
+
+/// Places in template that use macro `name` through `import`: name in
+/// `{% from %}` with calls, or `module.name` accesses. Alias and its calls
+/// are included only with `aliases`, renaming of macro doesn't change them.
+pub fn import_occurrences(
+    objects: &[JinjaObject],
+    variables: &[Identifier],
+    import: &Import,
+    name: &str,
+    aliases: bool,
+) -> Vec<(Point, Point)> {
+    let mut locations = vec![];
+    match import {
+        Import::From { .. } => {
+            for (imported, alias) in import.imported_names() {
+                if imported.name != name {
+                    continue;
+                }
+                locations.push((imported.start, imported.end));
+                match alias {
+                    None => locations.extend(unresolved_occurrences(objects, variables, name)),
+                    Some(alias) if aliases => {
+                        locations.push((alias.start, alias.end));
+                        locations.extend(unresolved_occurrences(objects, variables, &alias.name));
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        Import::Import { identifier, .. } => {
+            locations.extend(module_occurrences(objects, &identifier.name, name));
+        }
+        _ => {}
+    }
+    let mut unique = vec![];
+    for location in locations {
+        if !unique.contains(&location) {
+            unique.push(location);
+        }
+    }
+    unique
+}
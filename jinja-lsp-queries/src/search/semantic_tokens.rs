@@ -0,0 +1,121 @@
+use tower_lsp::lsp_types::{
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensLegend,
+};
+use tree_sitter::Point;
+
+use super::IdentifierType;
+
+pub const TOKEN_TYPES: [SemanticTokenType; 7] = [
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::MACRO,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::STRING,
+];
+
+pub const TOKEN_MODIFIERS: [SemanticTokenModifier; 4] = [
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::READONLY,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+    SemanticTokenModifier::new("undefined"),
+];
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JinjaToken {
+    pub start: Point,
+    pub end: Point,
+    pub token_type: SemanticTokenType,
+    pub modifiers: Vec<SemanticTokenModifier>,
+}
+
+impl JinjaToken {
+    pub fn new(location: (Point, Point), token_type: SemanticTokenType) -> Self {
+        Self {
+            start: location.0,
+            end: location.1,
+            token_type,
+            modifiers: vec![],
+        }
+    }
+
+    /// Token for identifier with known type, declarations are marked as such.
+    pub fn identifier(
+        location: (Point, Point),
+        identifier_type: &IdentifierType,
+        declaration: bool,
+    ) -> Self {
+        let mut token = Self::new(location, identifier_type.semantic_token_type());
+        match identifier_type {
+            IdentifierType::BackendVariable => {
+                token.modifiers.push(SemanticTokenModifier::READONLY);
+                token.modifiers.push(SemanticTokenModifier::DEFAULT_LIBRARY);
+            }
+            IdentifierType::UndefinedVariable => {
+                token
+                    .modifiers
+                    .push(SemanticTokenModifier::new("undefined"));
+            }
+            _ => {}
+        }
+        if declaration {
+            token.modifiers.push(SemanticTokenModifier::DECLARATION);
+        }
+        token
+    }
+
+    pub fn with_modifier(mut self, modifier: SemanticTokenModifier) -> Self {
+        self.modifiers.push(modifier);
+        self
+    }
+}
+
+/// Relative encoding of tokens. Tokens that span multiple lines or overlap
+/// with previous token are skipped, first token at some location wins.
+pub fn encode_tokens(mut tokens: Vec<JinjaToken>) -> Vec<SemanticToken> {
+    tokens.sort_by_key(|token| token.start);
+    let mut encoded = vec![];
+    let mut previous = Point::default();
+    let mut previous_end = Point::default();
+    for token in tokens {
+        if token.start.row != token.end.row || token.end <= token.start {
+            continue;
+        }
+        if !encoded.is_empty() && token.start < previous_end {
+            continue;
+        }
+        let Some(token_type) = TOKEN_TYPES.iter().position(|t| t == &token.token_type) else {
+            continue;
+        };
+        let mut bitset = 0;
+        for modifier in &token.modifiers {
+            if let Some(index) = TOKEN_MODIFIERS.iter().position(|m| m == modifier) {
+                bitset |= 1 << index;
+            }
+        }
+        let delta_line = token.start.row - previous.row;
+        let delta_start = if delta_line == 0 {
+            token.start.column - previous.column
+        } else {
+            token.start.column
+        };
+        encoded.push(SemanticToken {
+            delta_line: delta_line as u32,
+            delta_start: delta_start as u32,
+            length: (token.end.column - token.start.column) as u32,
+            token_type: token_type as u32,
+            token_modifiers_bitset: bitset,
+        });
+        previous = token.start;
+        previous_end = token.end;
+    }
+    encoded
+}
@@ -0,0 +1,335 @@
+use tree_sitter::Node;
+
+use super::Identifier;
+
+/// Macro call that is not closed before the cursor.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CallContext {
+    /// Name of macro, `forms.input` when called through imported module.
+    pub callee: String,
+    /// Position of argument under the cursor.
+    pub argument: usize,
+    /// Name of keyword argument under the cursor.
+    pub keyword: Option<String>,
+}
+
+/// Call with closed argument list, offsets are bytes in template.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MacroCall {
+    pub callee: String,
+    pub start: usize,
+    pub arguments: Vec<CallArgument>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CallArgument {
+    pub start: usize,
+    pub text: String,
+    pub keyword: Option<String>,
+}
+
+/// Name of macro parameter with its default value.
+pub type Parameter = (String, Option<String>);
+
+/// Argument that macro doesn't accept.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ArgumentError {
+    /// Positional argument after all parameters are taken.
+    TooMany,
+    /// Keyword that isn't name of any parameter.
+    UnknownKeyword,
+}
+
+/// Arguments of `call` that don't match `parameters`. Extra positional
+/// arguments are allowed with `varargs` and unknown keywords with `kwargs`,
+/// calls that unpack arguments are not checked.
+pub fn argument_errors<'a>(
+    call: &'a MacroCall,
+    parameters: &[String],
+    varargs: bool,
+    kwargs: bool,
+) -> Vec<(ArgumentError, &'a CallArgument)> {
+    if call
+        .arguments
+        .iter()
+        .any(|argument| argument.text.starts_with('*'))
+    {
+        return vec![];
+    }
+    let mut errors = vec![];
+    let mut positional = 0;
+    for argument in &call.arguments {
+        match &argument.keyword {
+            Some(keyword) => {
+                if !kwargs && !parameters.contains(keyword) {
+                    errors.push((ArgumentError::UnknownKeyword, argument));
+                }
+            }
+            None => {
+                positional += 1;
+                if !varargs && positional > parameters.len() {
+                    errors.push((ArgumentError::TooMany, argument));
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Parameter names of `{% macro name(a, b="x") %}` statement, identifiers
+/// in default values are skipped.
+pub fn parameter_names<'a>(statement: Node<'a>, source: &str) -> Vec<Node<'a>> {
+    let tokens = tokens(statement, source);
+    let Some(open) = tokens.iter().position(|token| token.is_char('(')) else {
+        return vec![];
+    };
+    let mut names = vec![];
+    let mut expects_name = true;
+    let mut depth = 0;
+    for token in &tokens[open + 1..] {
+        match token {
+            Token::Char('(' | '[' | '{', _) => depth += 1,
+            Token::Char(')' | ']' | '}', _) if depth == 0 => break,
+            Token::Char(')' | ']' | '}', _) => depth -= 1,
+            Token::Char(',', _) if depth == 0 => {
+                expects_name = true;
+                continue;
+            }
+            Token::Node(node) if expects_name && depth == 0 && node.kind() == "identifier" => {
+                names.push(*node);
+            }
+            _ => {}
+        }
+        expects_name = false;
+    }
+    names
+}
+
+/// `MacroParameter` identifiers of macro statement with their default values.
+pub fn macro_parameters(statement: Node, source: &str, names: &[Identifier]) -> Vec<Parameter> {
+    let tokens = tokens(statement, source);
+    names
+        .iter()
+        .map(|name| {
+            let index = tokens.iter().position(
+                |token| matches!(token, Token::Node(node) if node.start_position() == name.start),
+            );
+            let default = index.and_then(|index| {
+                let rest = tokens.get(index + 1..)?;
+                if !rest.first()?.is_char('=') {
+                    return None;
+                }
+                let value = &rest[1..1 + argument_end(&rest[1..])];
+                let (first, last) = (value.first()?, value.last()?);
+                Some(source.get(first.start()..last.end())?.to_string())
+            });
+            (name.name.to_owned(), default)
+        })
+        .collect()
+}
+
+/// Finds call around `offset` in statement or expression `node`.
+pub fn call_context(node: Node, source: &str, offset: usize) -> Option<CallContext> {
+    let mut cursor = node.walk();
+    let closed = node.children(&mut cursor).any(|child| {
+        matches!(child.kind(), "expression_end" | "statement_end")
+            && !child.is_missing()
+            && child.start_byte() < offset
+    });
+    if closed {
+        return None;
+    }
+    let tokens: Vec<Token> = tokens(node, source)
+        .into_iter()
+        .filter(|token| token.start() < offset)
+        .collect();
+    let mut open = vec![];
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Char(c @ ('(' | '[' | '{'), _) => open.push((index, *c)),
+            Token::Char(')' | ']' | '}', _) => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+    let (paren, _) = open.into_iter().rev().find(|(_, c)| *c == '(')?;
+    let callee = tokens.get(paren.checked_sub(1)?)?.name(source)?;
+    let arguments = split_arguments(&tokens[paren + 1..]);
+    let current = arguments.last().copied().unwrap_or_default();
+    Some(CallContext {
+        callee: callee.to_string(),
+        argument: arguments.len().saturating_sub(1),
+        keyword: keyword(current, source),
+    })
+}
+
+/// All calls in statement or expression `node`, including nested ones.
+pub fn macro_calls(node: Node, source: &str) -> Vec<MacroCall> {
+    let tokens = tokens(node, source);
+    let mut calls = vec![];
+    let mut open = vec![];
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Char(c @ ('(' | '[' | '{'), _) => open.push((index, *c)),
+            Token::Char(')' | ']' | '}', _) => {
+                let Some((paren, '(')) = open.pop() else {
+                    continue;
+                };
+                let Some(callee) = paren.checked_sub(1).map(|callee| tokens[callee]) else {
+                    continue;
+                };
+                let Some(name) = callee.name(source) else {
+                    continue;
+                };
+                let arguments = split_arguments(&tokens[paren + 1..index])
+                    .into_iter()
+                    .filter_map(|argument| {
+                        let (first, last) = (argument.first()?, argument.last()?);
+                        Some(CallArgument {
+                            start: first.start(),
+                            text: source.get(first.start()..last.end())?.to_string(),
+                            keyword: keyword(argument, source),
+                        })
+                    })
+                    .collect();
+                calls.push(MacroCall {
+                    callee: name.to_string(),
+                    start: callee.start(),
+                    arguments,
+                });
+            }
+            _ => {}
+        }
+    }
+    calls.sort_by_key(|call| call.start);
+    calls
+}
+
+/// `calls` grouped by what `callee` resolves them to, in order of first call.
+/// Calls that `callee` can't resolve are skipped.
+pub fn calls_by_callee<C, T: PartialEq>(
+    calls: impl IntoIterator<Item = C>,
+    callee: impl Fn(&C) -> Option<T>,
+) -> Vec<(T, Vec<C>)> {
+    let mut grouped: Vec<(T, Vec<C>)> = vec![];
+    for call in calls {
+        let Some(to) = callee(&call) else {
+            continue;
+        };
+        match grouped.iter_mut().find(|(target, _)| *target == to) {
+            Some((_, calls)) => calls.push(call),
+            None => grouped.push((to, vec![call])),
+        }
+    }
+    grouped
+}
+
+/// Node of statement or expression, operators are split into characters.
+#[derive(Clone, Copy)]
+enum Token<'a> {
+    Node(Node<'a>),
+    /// Character of operator with its byte offset.
+    Char(char, usize),
+}
+
+impl Token<'_> {
+    fn start(&self) -> usize {
+        match self {
+            Token::Node(node) => node.start_byte(),
+            Token::Char(_, offset) => *offset,
+        }
+    }
+
+    fn end(&self) -> usize {
+        match self {
+            Token::Node(node) => node.end_byte(),
+            Token::Char(c, offset) => offset + c.len_utf8(),
+        }
+    }
+
+    fn is_char(&self, c: char) -> bool {
+        matches!(self, Token::Char(token, _) if *token == c)
+    }
+
+    /// Name of variable or macro, numbers are not names.
+    fn name<'s>(&self, source: &'s str) -> Option<&'s str> {
+        let Token::Node(node) = self else {
+            return None;
+        };
+        if !matches!(node.kind(), "identifier" | "dotted_identifier") {
+            return None;
+        }
+        let text = node.utf8_text(source.as_bytes()).ok()?;
+        (!text.starts_with(|c: char| c.is_numeric())).then_some(text)
+    }
+}
+
+/// Tokens of `node` in order, without delimiters of statement or expression.
+fn tokens<'a>(node: Node<'a>, source: &str) -> Vec<Token<'a>> {
+    let mut tokens = vec![];
+    let mut nodes = vec![node];
+    while let Some(node) = nodes.pop() {
+        let text = node.utf8_text(source.as_bytes()).unwrap_or_default();
+        match node.kind() {
+            "expression_begin" | "expression_end" | "statement_begin" | "statement_end" => {}
+            "identifier" | "dotted_identifier" | "string" => tokens.push(Token::Node(node)),
+            _ if node.child_count() > 0 => {
+                let mut cursor = node.walk();
+                let children: Vec<Node> = node.children(&mut cursor).collect();
+                nodes.extend(children.into_iter().rev());
+            }
+            _ if text.is_empty() => {}
+            _ if text
+                .chars()
+                .all(|c| c.is_ascii_punctuation() && c != '"' && c != '\'') =>
+            {
+                let start = node.start_byte();
+                tokens.extend(
+                    text.char_indices()
+                        .map(|(index, c)| Token::Char(c, start + index)),
+                );
+            }
+            _ => tokens.push(Token::Node(node)),
+        }
+    }
+    tokens
+}
+
+/// Splits arguments on commas that are not nested in brackets.
+fn split_arguments<'t, 'a>(tokens: &'t [Token<'a>]) -> Vec<&'t [Token<'a>]> {
+    let mut arguments = vec![];
+    let mut start = 0;
+    while start <= tokens.len() {
+        let end = start + argument_end(&tokens[start..]);
+        arguments.push(&tokens[start..end]);
+        start = end + 1;
+    }
+    arguments
+}
+
+/// Index of comma or closing bracket that ends first argument of `tokens`.
+fn argument_end(tokens: &[Token]) -> usize {
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Char('(' | '[' | '{', _) => depth += 1,
+            Token::Char(')' | ']' | '}' | ',', _) if depth == 0 => return index,
+            Token::Char(')' | ']' | '}', _) => depth -= 1,
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+/// `name=value` argument, comparison operators are not keywords.
+fn keyword(argument: &[Token], source: &str) -> Option<String> {
+    let [name, equal, rest @ ..] = argument else {
+        return None;
+    };
+    if !equal.is_char('=') || rest.first().is_some_and(|token| token.is_char('=')) {
+        return None;
+    }
+    let name = name.name(source)?;
+    (!name.contains('.')).then(|| name.to_string())
+}
@@ -0,0 +1,89 @@
+use tree_sitter::{Node, Tree};
+
+use crate::tree_builder::JinjaDiagnostic;
+
+use super::Identifier;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum SyntaxError {
+    /// `{{`, `{%` or `{#` without closing delimiter.
+    Unclosed(String),
+    /// Token that parser inserted to recover, already described for message.
+    Missing(String),
+    /// Text that parser skipped.
+    Unexpected(String),
+}
+
+/// `ERROR` and `MISSING` nodes of template, each is reported once, nodes
+/// inside of them are skipped.
+pub fn syntax_errors(tree: &Tree, source: &str) -> Vec<(JinjaDiagnostic, Identifier)> {
+    let mut errors = vec![];
+    let mut cursor = tree.walk();
+    let mut nodes = vec![tree.root_node()];
+    while let Some(node) = nodes.pop() {
+        if node.is_error() {
+            errors.push(unexpected(node, source));
+        } else if node.is_missing() {
+            errors.push(missing(node, source));
+        } else if node.has_error() {
+            nodes.extend(node.children(&mut cursor));
+        }
+    }
+    errors.sort_by_key(|(_, identifier)| identifier.start);
+    errors
+}
+
+fn unexpected(node: Node, source: &str) -> (JinjaDiagnostic, Identifier) {
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.children(&mut cursor).collect();
+    if let Some(begin) = children.first()
+        && let Some(end) = closing_delimiter(begin.kind())
+        && !children.iter().any(|child| child.kind() == end)
+    {
+        return unclosed(*begin, source);
+    }
+    let text = node.utf8_text(source.as_bytes()).unwrap_or_default();
+    let text = text.lines().next().unwrap_or_default().trim();
+    let error = SyntaxError::Unexpected(text.to_owned());
+    let identifier = Identifier::new(text, node.start_position(), node.end_position());
+    (JinjaDiagnostic::SyntaxError(error), identifier)
+}
+
+fn missing(node: Node, source: &str) -> (JinjaDiagnostic, Identifier) {
+    let begin = node.parent().and_then(|parent| parent.child(0));
+    if let Some(begin) = begin
+        && closing_delimiter(begin.kind()) == Some(node.kind())
+    {
+        return unclosed(begin, source);
+    }
+    let description = if node.is_named() {
+        node.kind().replace('_', " ")
+    } else if node
+        .parent()
+        .is_some_and(|parent| parent.kind() == "keyword")
+    {
+        String::from("keyword")
+    } else {
+        format!("`{}`", node.kind())
+    };
+    let identifier = Identifier::new(&description, node.start_position(), node.end_position());
+    let error = SyntaxError::Missing(description);
+    (JinjaDiagnostic::SyntaxError(error), identifier)
+}
+
+fn unclosed(begin: Node, source: &str) -> (JinjaDiagnostic, Identifier) {
+    let delimiter = begin.utf8_text(source.as_bytes()).unwrap_or_default();
+    let identifier = Identifier::new(delimiter, begin.start_position(), begin.end_position());
+    let error = SyntaxError::Unclosed(delimiter.to_owned());
+    (JinjaDiagnostic::SyntaxError(error), identifier)
+}
+
+/// Kind of node that closes node of kind `begin`.
+fn closing_delimiter(begin: &str) -> Option<&'static str> {
+    match begin {
+        "expression_begin" => Some("expression_end"),
+        "statement_begin" => Some("statement_end"),
+        "{#" => Some("#}"),
+        _ => None,
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::{Identifier, render_context::RenderCall, templates::Import};
+use crate::tree_builder::JinjaDiagnostic;
+
+/// Kind of statement that uses other template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    Extends,
+    Include,
+    /// `{% import %}` and `{% from ... import %}`.
+    Import,
+}
+
+/// Statement of template that points to other template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateEdge {
+    /// Uri of template that statement uses.
+    pub uri: String,
+    /// Template name in statement.
+    pub template: Identifier,
+    pub kind: EdgeKind,
+}
+
+impl TemplateEdge {
+    /// Template from `{% extends %}` and `{% include %}` is rendered together
+    /// with template that uses it.
+    pub fn is_rendered(&self) -> bool {
+        matches!(self.kind, EdgeKind::Extends | EdgeKind::Include)
+    }
+}
+
+/// Templates linked by their statements and backend files that render them.
+/// Template names are resolved once, when statement or call is added.
+#[derive(Default, Debug, Clone)]
+pub struct TemplateGraph {
+    uris: HashMap<String, String>,
+    edges: HashMap<String, Vec<TemplateEdge>>,
+    /// Render calls of backend file, with uri of rendered template.
+    calls: HashMap<String, Vec<(String, RenderCall)>>,
+}
+
+impl TemplateGraph {
+    /// Replaces statements of template `uri`. `resolve` returns uri of
+    /// template name, or `None` if template doesn't exist.
+    pub fn set_imports(
+        &mut self,
+        uri: &str,
+        imports: &[Import],
+        resolve: impl Fn(&str) -> Option<String>,
+    ) {
+        let mut edges = vec![];
+        for import in imports {
+            let kind = match import {
+                Import::Extends { .. } => EdgeKind::Extends,
+                Import::Include { .. } => EdgeKind::Include,
+                Import::From { .. } | Import::Import { .. } => EdgeKind::Import,
+            };
+            for template in import.templates() {
+                if let Some(target) = self.resolve(&template.name, &resolve) {
+                    edges.push(TemplateEdge {
+                        uri: target,
+                        template: template.to_owned(),
+                        kind,
+                    });
+                }
+            }
+        }
+        self.edges.insert(uri.to_owned(), edges);
+    }
+
+    /// Replaces render calls of backend file `uri`.
+    pub fn set_calls(
+        &mut self,
+        uri: &str,
+        calls: &[RenderCall],
+        resolve: impl Fn(&str) -> Option<String>,
+    ) {
+        let mut rendered = vec![];
+        for call in calls {
+            if let Some(template) = self.resolve(&call.template.name, &resolve) {
+                rendered.push((template, call.to_owned()));
+            }
+        }
+        self.calls.insert(uri.to_owned(), rendered);
+    }
+
+    /// Forgets statements or calls of file `uri`.
+    pub fn remove(&mut self, uri: &str) {
+        self.edges.remove(uri);
+        self.calls.remove(uri);
+    }
+
+    fn resolve(&mut self, name: &str, resolve: impl Fn(&str) -> Option<String>) -> Option<String> {
+        if let Some(uri) = self.uris.get(name) {
+            return Some(uri.to_owned());
+        }
+        let uri = resolve(name)?;
+        self.uris.insert(name.to_owned(), uri.to_owned());
+        Some(uri)
+    }
+
+    /// Uri of template `name` that was already resolved.
+    pub fn uri(&self, name: &str) -> Option<&String> {
+        self.uris.get(name)
+    }
+
+    /// Statements of template `uri` that point to existing templates.
+    pub fn edges(&self, uri: &str) -> &[TemplateEdge] {
+        self.edges.get(uri).map_or(&[], |edges| edges.as_slice())
+    }
+
+    /// Statements in other templates that point to template `uri`.
+    pub fn dependents(&self, uri: &str) -> Vec<(&String, &TemplateEdge)> {
+        let mut dependents: Vec<(&String, &TemplateEdge)> = self
+            .edges
+            .iter()
+            .flat_map(|(template, edges)| edges.iter().map(move |edge| (template, edge)))
+            .filter(|(_, edge)| edge.uri == uri)
+            .collect();
+        dependents.sort_by(|a, b| (a.0, a.1.template.start).cmp(&(b.0, b.1.template.start)));
+        dependents
+    }
+
+    /// Templates that template `uri` extends.
+    pub fn parents(&self, uri: &str) -> Vec<&String> {
+        self.edges(uri)
+            .iter()
+            .filter(|edge| edge.kind == EdgeKind::Extends)
+            .map(|edge| &edge.uri)
+            .collect()
+    }
+
+    /// Templates that extend template `uri`, sorted.
+    pub fn children(&self, uri: &str) -> Vec<&String> {
+        let mut children: Vec<&String> = self
+            .dependents(uri)
+            .into_iter()
+            .filter(|(_, edge)| edge.kind == EdgeKind::Extends)
+            .map(|(template, _)| template)
+            .collect();
+        children.dedup();
+        children
+    }
+
+    /// Templates connected to template `uri` through `{% extends %}`, in both
+    /// directions, with `uri` itself.
+    pub fn extends_family(&self, uri: &str) -> HashSet<String> {
+        let mut family = HashSet::from([uri.to_owned()]);
+        let mut stack = vec![uri.to_owned()];
+        while let Some(current) = stack.pop() {
+            let next: Vec<String> = self
+                .parents(&current)
+                .into_iter()
+                .chain(self.children(&current))
+                .cloned()
+                .collect();
+            for template in next {
+                if family.insert(template.to_owned()) {
+                    stack.push(template);
+                }
+            }
+        }
+        family
+    }
+
+    /// Closest template up the `{% extends %}` chain of template `uri` for
+    /// which `has_block` is true.
+    pub fn parent_block(&self, uri: &str, has_block: impl Fn(&str) -> bool) -> Option<String> {
+        let mut visited = HashSet::from([uri]);
+        let mut current = self.parents(uri).first().copied()?;
+        while visited.insert(current) {
+            if has_block(current) {
+                return Some(current.to_owned());
+            }
+            current = self.parents(current).first().copied()?;
+        }
+        None
+    }
+
+    /// Templates with the same block as template `uri`: the first one up the
+    /// `{% extends %}` chain that defines it and every template below that
+    /// overrides it. `has_block` tells if template defines block. Sorted.
+    pub fn block_overrides(&self, uri: &str, has_block: impl Fn(&str) -> bool) -> Vec<String> {
+        let mut root = uri;
+        let mut visited = HashSet::from([uri]);
+        let mut current = uri;
+        while let Some(parent) = self.parents(current).first().copied() {
+            if !visited.insert(parent) {
+                break;
+            }
+            if has_block(parent) {
+                root = parent;
+            }
+            current = parent;
+        }
+        let mut templates = vec![];
+        let mut visited = HashSet::from([root]);
+        let mut stack = vec![root];
+        while let Some(template) = stack.pop() {
+            if has_block(template) {
+                templates.push(template.to_owned());
+            }
+            for child in self.children(template) {
+                if visited.insert(child) {
+                    stack.push(child);
+                }
+            }
+        }
+        templates.sort();
+        templates
+    }
+
+    /// Backend calls that render template `uri`, with uri of backend file.
+    pub fn render_calls(&self, uri: &str) -> Vec<(&String, &RenderCall)> {
+        let mut calls: Vec<(&String, &RenderCall)> = self
+            .calls
+            .iter()
+            .flat_map(|(file, calls)| calls.iter().map(move |call| (file, call)))
+            .filter(|(_, (template, _))| template == uri)
+            .map(|(file, (_, call))| (file, call))
+            .collect();
+        calls.sort_by(|a, b| (a.0, a.1.template.start).cmp(&(b.0, b.1.template.start)));
+        calls
+    }
+
+    /// Rendered template of every call in backend file `uri`.
+    pub fn calls_of(&self, uri: &str) -> &[(String, RenderCall)] {
+        self.calls.get(uri).map_or(&[], |calls| calls.as_slice())
+    }
+
+    /// Template `uri` and templates it includes or extends, they are rendered
+    /// together.
+    pub fn rendered_templates(&self, uri: &str) -> Vec<String> {
+        let mut templates = vec![uri.to_owned()];
+        let mut index = 0;
+        while let Some(current) = templates.get(index) {
+            let next: Vec<String> = self
+                .edges(current)
+                .iter()
+                .filter(|edge| edge.is_rendered() && !templates.contains(&edge.uri))
+                .map(|edge| edge.uri.to_owned())
+                .collect();
+            for template in next {
+                if !templates.contains(&template) {
+                    templates.push(template);
+                }
+            }
+            index += 1;
+        }
+        templates
+    }
+
+    /// Template `uri` and templates that include or extend it, rendering any
+    /// of them renders `uri` too.
+    pub fn rendering_templates(&self, uri: &str) -> Vec<String> {
+        let mut templates = vec![uri.to_owned()];
+        let mut index = 0;
+        while let Some(current) = templates.get(index) {
+            let next: Vec<String> = self
+                .dependents(current)
+                .into_iter()
+                .filter(|(template, edge)| edge.is_rendered() && !templates.contains(template))
+                .map(|(template, _)| template.to_owned())
+                .collect();
+            for template in next {
+                if !templates.contains(&template) {
+                    templates.push(template);
+                }
+            }
+            index += 1;
+        }
+        templates
+    }
+
+    /// Names defined for template `uri` by backend: `globals` and context of
+    /// calls that render it (directly or through `{% include %}` and
+    /// `{% extends %}`). `None` if it isn't rendered or if some call passes
+    /// dynamic context.
+    pub fn template_context(
+        &self,
+        uri: &str,
+        globals: &HashSet<String>,
+    ) -> Option<HashSet<String>> {
+        let mut names = globals.to_owned();
+        let mut is_rendered = false;
+        for template in self.rendering_templates(uri) {
+            for (_, call) in self.render_calls(&template) {
+                if call.dynamic {
+                    return None;
+                }
+                is_rendered = true;
+                names.extend(call.context.iter().map(|key| key.name.to_owned()));
+            }
+        }
+        is_rendered.then_some(names)
+    }
+
+    /// Backend files that pass each context key to template `uri`, through
+    /// calls that render it.
+    pub fn context_sources(&self, uri: &str) -> HashMap<String, Vec<String>> {
+        let mut sources: HashMap<String, Vec<String>> = HashMap::new();
+        for template in self.rendering_templates(uri) {
+            for (file, call) in self.render_calls(&template) {
+                for key in &call.context {
+                    let files = sources.entry(key.name.to_owned()).or_default();
+                    if !files.contains(file) {
+                        files.push(file.to_owned());
+                    }
+                }
+            }
+        }
+        sources
+    }
+
+    /// Statements of template `uri` that lead back to it, each with the
+    /// shortest cycle it belongs to.
+    pub fn cycles(&self, uri: &str) -> Vec<(JinjaDiagnostic, Identifier)> {
+        let mut diagnostics = vec![];
+        for edge in self.edges(uri) {
+            // statement that reaches template, from breadth-first search
+            let mut previous: HashMap<&String, (&String, &Identifier)> = HashMap::new();
+            let mut queue = VecDeque::from([&edge.uri]);
+            let mut found = edge.uri == uri;
+            while let Some(current) = queue.pop_front()
+                && !found
+            {
+                for next in self.edges(current) {
+                    if next.uri == edge.uri || previous.contains_key(&next.uri) {
+                        continue;
+                    }
+                    previous.insert(&next.uri, (current, &next.template));
+                    if next.uri == uri {
+                        found = true;
+                        break;
+                    }
+                    queue.push_back(&next.uri);
+                }
+            }
+            if !found {
+                continue;
+            }
+            let mut statements = vec![];
+            let uri = uri.to_owned();
+            let mut current = &uri;
+            while let Some((from, template)) = previous.remove(current) {
+                statements.push((from.to_owned(), template.to_owned()));
+                current = from;
+            }
+            statements.push((uri, edge.template.to_owned()));
+            statements.reverse();
+            diagnostics.push((JinjaDiagnostic::Cycle(statements), edge.template.to_owned()));
+        }
+        diagnostics
+    }
+
+    /// Templates that are reachable from template `uri` or that reach it
+    /// through extends, include and import statements.
+    pub fn linked_templates(&self, uri: &str) -> Vec<String> {
+        let mut templates = vec![];
+        let mut queue = vec![uri.to_owned()];
+        while let Some(current) = queue.pop() {
+            for edge in self.edges(&current) {
+                if edge.uri != uri && !templates.contains(&edge.uri) {
+                    templates.push(edge.uri.to_owned());
+                    queue.push(edge.uri.to_owned());
+                }
+            }
+        }
+        let mut queue = vec![uri.to_owned()];
+        while let Some(current) = queue.pop() {
+            for (template, _) in self.dependents(&current) {
+                if template != uri && !templates.contains(template) {
+                    templates.push(template.to_owned());
+                    queue.push(template.to_owned());
+                }
+            }
+        }
+        templates
+    }
+}
@@ -4,7 +4,7 @@ use tree_sitter::{Point, Query, QueryCapture, QueryCursor, StreamingIterator, Tr
 
 use super::{Identifier, IdentifierType};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Import {
     Extends {
         template: Identifier,
@@ -43,6 +43,36 @@ impl Import {
         }
     }
 
+    pub fn templates(&self) -> Vec<&Identifier> {
+        match &self {
+            Import::Extends { template }
+            | Import::From { template, .. }
+            | Import::Import { template, .. } => vec![template],
+            Import::Include { templates } => templates.iter().collect(),
+        }
+    }
+
+    /// Names from `{% from "..." import name as alias, other %}` paired with their alias.
+    pub fn imported_names(&self) -> Vec<(&Identifier, Option<&Identifier>)> {
+        let mut names = vec![];
+        if let Import::From { identifiers, .. } = &self {
+            let mut index = 0;
+            while let Some(name) = identifiers.get(index) {
+                let alias = identifiers
+                    .get(index + 1)
+                    .filter(|keyword| keyword.name == "as")
+                    .and_then(|_| identifiers.get(index + 2));
+                if alias.is_some() {
+                    index += 3;
+                } else {
+                    index += 1;
+                }
+                names.push((name, alias));
+            }
+        }
+        names
+    }
+
     fn collect(self, ids: &mut Vec<Identifier>) {
         match self {
             Import::Extends { template } => ids.push(template),
@@ -204,6 +234,8 @@ impl JinjaImports {
                         Import::Import { identifier, .. } => {
                             if identifier.name.is_empty() {
                                 identifier.name = String::from(name);
+                                identifier.start = start;
+                                identifier.end = end;
                                 self.current = Current::Nothing;
                             }
                         }
@@ -222,7 +254,42 @@ impl JinjaImports {
             i.1.collect(ids);
         }
     }
+
+    pub fn show(self) -> Vec<Import> {
+        let mut imports: Vec<Import> = self.imports.into_values().collect();
+        imports.sort_by_key(|import| import.templates().first().map(|template| template.start));
+        imports
+    }
 }
+/// Template and name of macro that is called as `callee`, imported with
+/// `{% import "..." as module %}` or `{% from "..." import name as alias %}`.
+pub fn imported_macro_name<'a>(
+    imports: &'a [Import],
+    callee: &'a str,
+) -> Option<(&'a Identifier, &'a str)> {
+    match callee.split_once('.') {
+        Some((module, name)) => imports.iter().find_map(|import| match import {
+            Import::Import {
+                template,
+                identifier,
+            } if identifier.name == module => Some((template, name)),
+            _ => None,
+        }),
+        None => imports.iter().find_map(|import| {
+            let Import::From { template, .. } = import else {
+                return None;
+            };
+            import
+                .imported_names()
+                .into_iter()
+                .find_map(|(name, alias)| {
+                    let imported_as = alias.unwrap_or(name);
+                    (imported_as.name == callee).then_some((template, name.name.as_str()))
+                })
+        }),
+    }
+}
+
 pub fn templates_query(
     query: &Query,
     tree: &Tree,
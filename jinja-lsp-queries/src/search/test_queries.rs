@@ -1,19 +1,48 @@
 #[cfg(test)]
 mod query_tests {
     use crate::{
+        builtins::{builtin_filters, builtin_tests},
+        formatter::{FormatOptions, format_template},
+        lsp_helper::{
+            context_names, dependent_lenses, document_links, folding_ranges, nested_symbols,
+            renamed_template, template_dependents, unknown_filters,
+        },
         search::{
             definition::definition_query,
             objects::{CompletionType, objects_query},
             python_identifiers::python_identifiers,
+            render_context::{
+                BackendContext, RenderCall, context_insertion, context_removal, missing_names,
+                render_context, unused_keys,
+            },
+            resolve::{
+                definition_occurrences, import_occurrences, imported_name_at, linked_ranges,
+                macro_callee, matching_statements, resolve_definition,
+            },
+            semantic_tokens::{JinjaToken, encode_tokens},
+            signature::{
+                ArgumentError, CallContext, MacroCall, argument_errors, call_context,
+                calls_by_callee, macro_calls, macro_parameters,
+            },
             snippets_completion::snippets_query,
+            syntax::syntax_errors,
+            template_graph::TemplateGraph,
         },
         to_input_edit::remove_unicode_content,
+        tree_builder::JinjaDiagnostic,
+    };
+    use std::{collections::HashSet, path::PathBuf};
+    use tower_lsp::lsp_types::{
+        DocumentSymbol, FoldingRangeKind, Position, Range, SemanticTokenType,
     };
     use tree_sitter::{Parser, Point};
 
     use crate::search::{
-        completion_start, queries::Queries, rust_identifiers::backend_definition_query,
-        rust_template_completion::backend_templates_query, templates::templates_query,
+        Identifier, IdentifierType, completion_start, fuzzy_score,
+        queries::Queries,
+        rust_identifiers::backend_definition_query,
+        rust_template_completion::backend_templates_query,
+        templates::{imported_macro_name, templates_query},
     };
 
     fn prepare_jinja_tree(text: &str) -> tree_sitter::Tree {
@@ -49,6 +78,35 @@ mod query_tests {
         parser.parse(text, None).expect("not to fail")
     }
 
+    fn prepare_backend_context(lang: &str, source: &str) -> (tree_sitter::Tree, BackendContext) {
+        let tree = match lang {
+            "rust" => prepare_rust_tree(source),
+            _ => prepare_python_tree(source),
+        };
+        let trigger_point = Point::new(0, 0);
+        let mut queries = Queries::default();
+        queries.update_backend(lang);
+        let mut variables = backend_definition_query(
+            &queries.backend_definitions,
+            &tree,
+            trigger_point,
+            source,
+            true,
+        )
+        .show();
+        let mut templates = backend_templates_query(
+            &queries.backend_templates,
+            &tree,
+            trigger_point,
+            source,
+            true,
+        )
+        .collect();
+        variables.append(&mut templates);
+        let context = render_context(&tree, source, &variables);
+        (tree, context)
+    }
+
     #[test]
     fn jinja_definitions1() {
         let cases = [
@@ -261,6 +319,39 @@ mod query_tests {
         assert_eq!(rust.show().len(), 8);
     }
 
+    #[test]
+    fn registered_in_rust_environment() {
+        let source = r#"
+            fn setup(env: &mut Environment) {
+                env.add_filter("slugify", slugify);
+                self.env.add_test("prime", is_prime);
+                env.add_global("site", "Title");
+                env.add_function("format_date", "unused");
+                env.add_template("page.jinja", "source");
+            }
+        "#;
+        let tree = prepare_rust_tree(source);
+        let query = Queries::default();
+        let variables = backend_definition_query(
+            &query.backend_definitions,
+            &tree,
+            Point::default(),
+            source,
+            true,
+        )
+        .show();
+        let context = render_context(&tree, source, &variables);
+        let names = |identifiers: &[Identifier]| -> Vec<String> {
+            identifiers
+                .iter()
+                .map(|identifier| identifier.name.to_owned())
+                .collect()
+        };
+        assert_eq!(names(&context.filters), vec!["slugify"]);
+        assert_eq!(names(&context.tests), vec!["prime"]);
+        assert_eq!(names(&context.globals), vec!["site", "format_date"]);
+    }
+
     #[test]
     fn python_definition() {
         let case = r#"
@@ -567,4 +658,1503 @@ mod query_tests {
         assert_eq!(location.0, Point::new(0, 12));
         assert_eq!(location.1, Point::new(0, 24));
     }
+
+    #[test]
+    fn rename_occurrences_respect_scope() {
+        let source = r#"
+            {% set name = 1 %}
+            {{ name }}
+            {% for name in items %}
+                {{ name.first }}
+            {% endfor %}
+            {{ name }}
+            {% block content %}{% endblock content %}
+        "#;
+        let query = Queries::default();
+        let tree = prepare_jinja_tree(source);
+        let definitions = definition_query(
+            &query.jinja_definitions,
+            &tree,
+            Point::default(),
+            source,
+            true,
+        );
+        assert_eq!(definitions.block_ends.len(), 1);
+        let variables = definitions.collect();
+        let objects =
+            objects_query(&query.jinja_objects, &tree, Point::default(), source, true).objects;
+
+        let outer = resolve_definition(&variables, "name", Point::new(2, 16)).unwrap();
+        let occurrences = definition_occurrences(&objects, &variables, outer);
+        assert_eq!(
+            occurrences,
+            vec![
+                (Point::new(1, 19), Point::new(1, 23)),
+                (Point::new(2, 15), Point::new(2, 19)),
+                (Point::new(6, 15), Point::new(6, 19)),
+            ]
+        );
+
+        let inner = resolve_definition(&variables, "name", Point::new(4, 20)).unwrap();
+        let occurrences = definition_occurrences(&objects, &variables, inner);
+        assert_eq!(occurrences.len(), 2);
+        assert!(resolve_definition(&variables, "content", Point::new(7, 25)).is_none());
+    }
+
+    #[test]
+    fn filter_names_and_arguments() {
+        let source = "{{ x\n | default(y | upper) | trim }}";
+        let tree = prepare_jinja_tree(source);
+        let query = Queries::default();
+        let objects =
+            objects_query(&query.jinja_objects, &tree, Point::default(), source, true).objects;
+        let objects: Vec<(&str, bool)> = objects
+            .iter()
+            .map(|object| (object.name.as_str(), object.is_filter))
+            .collect();
+        assert_eq!(
+            objects,
+            vec![
+                ("x", false),
+                ("default", true),
+                ("y", false),
+                ("upper", true),
+                ("trim", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn template_names_after_rename() {
+        let root = PathBuf::from("/project/templates");
+        let renamed = vec![
+            (root.join("partials"), root.join("components")),
+            (root.join("base.html"), root.join("layouts/base.html")),
+        ];
+        let name = |path: &str| renamed_template(&root, &root.join(path), &renamed);
+        assert_eq!(
+            name("partials/forms/input.html"),
+            Some(String::from("components/forms/input.html"))
+        );
+        assert_eq!(name("base.html"), Some(String::from("layouts/base.html")));
+        assert_eq!(name("partials.html"), None);
+        assert_eq!(name("page.html"), None);
+        let outside = vec![(root.join("base.html"), PathBuf::from("/tmp/base.html"))];
+        assert_eq!(
+            renamed_template(&root, &root.join("base.html"), &outside),
+            None
+        );
+    }
+
+    #[test]
+    fn macros_called_through_imports() {
+        let source = r#"{% import "forms.html" as forms %}
+{% from "fields.html" import input as field, label %}"#;
+        let tree = prepare_jinja_tree(source);
+        let query = Queries::default();
+        let imports = templates_query(&query.jinja_imports, &tree, Point::default(), source, true);
+        let imports = imports.show();
+        let resolve = |callee| {
+            imported_macro_name(&imports, callee)
+                .map(|(template, name)| (template.name.as_str(), name))
+        };
+        assert_eq!(resolve("forms.input"), Some(("forms.html", "input")));
+        assert_eq!(resolve("field"), Some(("fields.html", "input")));
+        assert_eq!(resolve("label"), Some(("fields.html", "label")));
+        assert_eq!(resolve("input"), None);
+        assert_eq!(resolve("other.input"), None);
+    }
+
+    #[test]
+    fn macro_from_other_template() {
+        let forms = r#"{% macro input(name) %}{% endmacro %}"#;
+        let page = r#"{% from "forms.html" import input as field %}{% import "forms.html" as forms %}
+{{ field("a") }} {{ forms.input("b") }} {% set title = 1 %}{{ title }}"#;
+        let queries = Queries::default();
+        let definitions = |source: &str| {
+            let tree = prepare_jinja_tree(source);
+            definition_query(
+                &queries.jinja_definitions,
+                &tree,
+                Point::default(),
+                source,
+                true,
+            )
+            .collect()
+        };
+        let forms_variables = definitions(forms);
+        let variables = definitions(page);
+        let tree = prepare_jinja_tree(page);
+        let objects = objects_query(&queries.jinja_objects, &tree, Point::default(), page, true);
+        let objects = objects.objects;
+        let imports = templates_query(&queries.jinja_imports, &tree, Point::default(), page, true);
+        let imports = imports.show();
+
+        let callee = |point| macro_callee(&objects, &variables, point);
+        assert_eq!(callee(Point::new(1, 4)), Some(String::from("field")));
+        assert_eq!(callee(Point::new(1, 26)), Some(String::from("forms.input")));
+        assert_eq!(callee(Point::new(1, 62)), None);
+        for point in [Point::new(1, 4), Point::new(1, 26), Point::new(0, 38)] {
+            let called = callee(point).unwrap_or_default();
+            let (template, name) = match imported_name_at(&imports, point) {
+                Some((template, name)) => (template, name.name.as_str()),
+                None => imported_macro_name(&imports, &called).unwrap(),
+            };
+            assert_eq!(template.name, "forms.html");
+            let definition = forms_variables.iter().find(|variable| {
+                variable.identifier_type == IdentifierType::MacroName && variable.name == name
+            });
+            assert_eq!(
+                definition.map(|definition| definition.start),
+                Some(Point::new(0, 9))
+            );
+        }
+
+        let occurrences = |import, aliases| {
+            import_occurrences(&objects, &variables, import, "input", aliases)
+                .into_iter()
+                .map(|(start, end)| (start.row, start.column, end.column))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            occurrences(&imports[0], true),
+            vec![(0, 28, 33), (0, 37, 42), (1, 3, 8)]
+        );
+        assert_eq!(occurrences(&imports[0], false), vec![(0, 28, 33)]);
+        assert_eq!(occurrences(&imports[1], true), vec![(1, 26, 31)]);
+    }
+
+    /// Graph of templates from `(name, source)` pairs, uri of template is
+    /// `file:///t/name`.
+    fn prepare_template_graph(templates: &[(&str, &str)]) -> TemplateGraph {
+        let query = Queries::default();
+        let mut graph = TemplateGraph::default();
+        let resolve = |name: &str| {
+            templates
+                .iter()
+                .any(|(template, _)| *template == name)
+                .then(|| format!("file:///t/{name}"))
+        };
+        for (name, source) in templates {
+            let tree = prepare_jinja_tree(source);
+            let imports =
+                templates_query(&query.jinja_imports, &tree, Point::default(), source, true);
+            graph.set_imports(&format!("file:///t/{name}"), &imports.show(), resolve);
+        }
+        graph
+    }
+
+    #[test]
+    fn document_links_to_existing_templates() {
+        let source = r#"{% extends "base.html" %}
+{% include "missing.html" %}{% from "forms.html" import input %}"#;
+        let tree = prepare_jinja_tree(source);
+        let query = Queries::default();
+        let imports = templates_query(&query.jinja_imports, &tree, Point::default(), source, true);
+        let templates: Vec<Identifier> = imports
+            .show()
+            .iter()
+            .flat_map(|import| import.templates())
+            .cloned()
+            .collect();
+        let resolve = |name: &str| (name != "missing.html").then(|| format!("file:///t/{name}"));
+        let links: Vec<(String, Range, String)> = document_links(templates, resolve)
+            .into_iter()
+            .map(|link| {
+                let target = link.target.map(String::from).unwrap_or_default();
+                (link.tooltip.unwrap_or_default(), link.range, target)
+            })
+            .collect();
+        let range =
+            |line, start, end| Range::new(Position::new(line, start), Position::new(line, end));
+        assert_eq!(
+            links,
+            vec![
+                (
+                    String::from("base.html"),
+                    range(0, 12, 21),
+                    String::from("file:///t/base.html")
+                ),
+                (
+                    String::from("forms.html"),
+                    range(1, 37, 47),
+                    String::from("file:///t/forms.html")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn code_lenses_of_template_dependents() {
+        let mut graph = prepare_template_graph(&[
+            ("base.html", "{% block body %}{% endblock %}"),
+            ("page.html", r#"{% extends "base.html" %}"#),
+            (
+                "list.html",
+                r#"{% extends "base.html" %}{% include "base.html" %}"#,
+            ),
+            ("forms.html", r#"{% import "base.html" as base %}"#),
+        ]);
+        let call = RenderCall {
+            template: Identifier::new("base.html", Point::new(3, 17), Point::new(3, 28)),
+            ..Default::default()
+        };
+        let resolve = |name: &str| Some(format!("file:///t/{name}"));
+        graph.set_calls("file:///t/app.py", &[call], resolve);
+        let dependents = template_dependents(&graph, "file:///t/base.html");
+        let lenses: Vec<(String, Vec<String>)> = dependent_lenses(dependents)
+            .into_iter()
+            .map(|(title, locations)| {
+                let files = locations
+                    .into_iter()
+                    .map(|location| format!("{}:{}", location.uri, location.range.start.line))
+                    .collect();
+                (title, files)
+            })
+            .collect();
+        assert_eq!(
+            lenses,
+            vec![
+                (
+                    String::from("rendered by 1 handler"),
+                    vec![String::from("file:///t/app.py:3")]
+                ),
+                (
+                    String::from("included by 1 template"),
+                    vec![String::from("file:///t/list.html:0")]
+                ),
+                (
+                    String::from("imported by 1 template"),
+                    vec![String::from("file:///t/forms.html:0")]
+                ),
+                (
+                    String::from("extended by 2 templates"),
+                    vec![
+                        String::from("file:///t/list.html:0"),
+                        String::from("file:///t/page.html:0")
+                    ]
+                ),
+            ]
+        );
+        let dependents = template_dependents(&graph, "file:///t/page.html");
+        assert!(dependent_lenses(dependents).is_empty());
+    }
+
+    #[test]
+    fn template_hierarchy() {
+        let graph = prepare_template_graph(&[
+            ("base.html", r#"{% include "nav.html" %}"#),
+            ("nav.html", ""),
+            ("page.html", r#"{% extends "base.html" %}"#),
+            (
+                "post.html",
+                r#"{% import "nav.html" as nav %}{% extends "page.html" %}"#,
+            ),
+            ("about.html", r#"{% extends "base.html" %}"#),
+        ]);
+        assert!(graph.parents("file:///t/base.html").is_empty());
+        assert_eq!(
+            graph.parents("file:///t/post.html"),
+            vec!["file:///t/page.html"]
+        );
+        assert_eq!(
+            graph.children("file:///t/base.html"),
+            vec!["file:///t/about.html", "file:///t/page.html"]
+        );
+        assert_eq!(
+            graph.children("file:///t/page.html"),
+            vec!["file:///t/post.html"]
+        );
+        assert!(graph.children("file:///t/nav.html").is_empty());
+    }
+
+    #[test]
+    fn block_chain() {
+        let templates = [
+            ("base.html", "{% block body %}{% endblock %}"),
+            ("page.html", r#"{% extends "base.html" %}"#),
+            (
+                "post.html",
+                r#"{% extends "page.html" %}{% block body %}{% endblock %}"#,
+            ),
+            (
+                "about.html",
+                r#"{% extends "base.html" %}{% block body %}{% endblock %}"#,
+            ),
+            ("nav.html", r#"{% include "base.html" %}"#),
+            (
+                "a.html",
+                r#"{% extends "b.html" %}{% block body %}{% endblock %}"#,
+            ),
+            ("b.html", r#"{% extends "a.html" %}"#),
+        ];
+        let graph = prepare_template_graph(&templates);
+        let has_block = |uri: &str| {
+            templates
+                .iter()
+                .any(|(name, source)| uri.ends_with(name) && source.contains("block body"))
+        };
+        assert_eq!(
+            graph.parent_block("file:///t/post.html", has_block),
+            Some(String::from("file:///t/base.html"))
+        );
+        assert_eq!(graph.parent_block("file:///t/base.html", has_block), None);
+        assert_eq!(graph.parent_block("file:///t/a.html", has_block), None);
+        let all = vec![
+            "file:///t/about.html",
+            "file:///t/base.html",
+            "file:///t/post.html",
+        ];
+        assert_eq!(graph.block_overrides("file:///t/post.html", has_block), all);
+        assert_eq!(graph.block_overrides("file:///t/base.html", has_block), all);
+        assert_eq!(
+            graph.block_overrides("file:///t/b.html", has_block),
+            vec!["file:///t/a.html"]
+        );
+        let mut family: Vec<String> = graph
+            .extends_family("file:///t/page.html")
+            .into_iter()
+            .collect();
+        family.sort();
+        assert_eq!(
+            family,
+            vec![
+                "file:///t/about.html",
+                "file:///t/base.html",
+                "file:///t/page.html",
+                "file:///t/post.html",
+            ]
+        );
+    }
+
+    #[test]
+    fn template_cycles() {
+        let graph = prepare_template_graph(&[
+            ("a.html", r#"{% extends "a.html" %}"#),
+            ("b.html", r#"{% include "c.html" %}"#),
+            ("c.html", r#"{% include "d.html" %}"#),
+            (
+                "d.html",
+                r#"{% include "b.html" %}
+{% import "e.html" as e %}{% include "missing.html" %}"#,
+            ),
+            ("e.html", ""),
+        ]);
+        let cycles = graph.cycles("file:///t/a.html");
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(
+            cycles[0].0.to_string(),
+            "Cyclic template dependency: `a.html` -> `a.html`"
+        );
+        assert_eq!(cycles[0].0.related_information(), None);
+
+        let cycles = graph.cycles("file:///t/b.html");
+        assert_eq!(cycles.len(), 1);
+        let (diagnostic, template) = &cycles[0];
+        assert_eq!(template.name, "c.html");
+        assert_eq!(
+            diagnostic.to_string(),
+            "Cyclic template dependency: `b.html` -> `c.html` -> `d.html` -> `b.html`"
+        );
+        let related: Vec<(String, u32, String)> = diagnostic
+            .related_information()
+            .unwrap()
+            .into_iter()
+            .map(|related| {
+                let location = related.location;
+                (
+                    location.uri.to_string(),
+                    location.range.start.character,
+                    related.message,
+                )
+            })
+            .collect();
+        assert_eq!(
+            related,
+            vec![
+                (
+                    String::from("file:///t/c.html"),
+                    11,
+                    String::from("Cycle continues with `d.html`")
+                ),
+                (
+                    String::from("file:///t/d.html"),
+                    11,
+                    String::from("Cycle continues with `b.html`")
+                ),
+            ]
+        );
+        assert!(matches!(
+            graph.cycles("file:///t/d.html").as_slice(),
+            [(JinjaDiagnostic::Cycle(statements), _)] if statements.len() == 3
+        ));
+        assert!(graph.cycles("file:///t/e.html").is_empty());
+        let mut linked = graph.linked_templates("file:///t/e.html");
+        linked.sort();
+        assert_eq!(
+            linked,
+            vec!["file:///t/b.html", "file:///t/c.html", "file:///t/d.html"]
+        );
+    }
+
+    #[test]
+    fn context_sources_of_rendered_template() {
+        let mut graph = prepare_template_graph(&[
+            ("base.html", "{{ title }}"),
+            ("page.html", r#"{% extends "base.html" %}"#),
+            ("other.html", "{{ title }}"),
+        ]);
+        let call = |template: &str, keys: &[&str]| RenderCall {
+            template: Identifier::new(template, Point::default(), Point::default()),
+            context: keys
+                .iter()
+                .map(|key| Identifier::new(key, Point::default(), Point::default()))
+                .collect(),
+            ..Default::default()
+        };
+        let resolve = |name: &str| Some(format!("file:///t/{name}"));
+        graph.set_calls(
+            "file:///t/app.py",
+            &[call("page.html", &["title", "user"])],
+            resolve,
+        );
+        graph.set_calls(
+            "file:///t/views.py",
+            &[call("base.html", &["title"])],
+            resolve,
+        );
+        // other backend file passes `title` to template that isn't rendered
+        graph.set_calls(
+            "file:///t/admin.py",
+            &[call("other.html", &["title"])],
+            resolve,
+        );
+        let sources = graph.context_sources("file:///t/base.html");
+        assert_eq!(sources.len(), 2);
+        assert_eq!(
+            sources["title"],
+            vec!["file:///t/views.py", "file:///t/app.py"]
+        );
+        assert_eq!(sources["user"], vec!["file:///t/app.py"]);
+    }
+
+    #[test]
+    fn context_of_rendered_templates() {
+        let mut graph = prepare_template_graph(&[
+            ("base.html", "{{ title }}"),
+            ("page.html", r#"{% extends "base.html" %}"#),
+            ("list.html", r#"{% include "base.html" %}"#),
+            ("other.html", ""),
+        ]);
+        let call = |template: &str, keys: &[&str]| RenderCall {
+            template: Identifier::new(template, Point::default(), Point::default()),
+            context: keys
+                .iter()
+                .map(|key| Identifier::new(key, Point::default(), Point::default()))
+                .collect(),
+            ..Default::default()
+        };
+        let resolve = |name: &str| Some(format!("file:///t/{name}"));
+        graph.set_calls(
+            "file:///t/app.py",
+            &[call("page.html", &["title"]), call("missing.html", &[])],
+            resolve,
+        );
+        let globals = HashSet::from([String::from("user")]);
+        let context = graph.template_context("file:///t/base.html", &globals);
+        assert_eq!(
+            context,
+            Some(HashSet::from([String::from("user"), String::from("title")]))
+        );
+        assert_eq!(
+            graph.template_context("file:///t/other.html", &globals),
+            None
+        );
+        assert_eq!(
+            graph.rendered_templates("file:///t/page.html"),
+            vec!["file:///t/page.html", "file:///t/base.html"]
+        );
+        // include passes context that backend doesn't know
+        let dynamic = RenderCall {
+            dynamic: true,
+            ..call("list.html", &[])
+        };
+        graph.set_calls("file:///t/views.py", &[dynamic], resolve);
+        assert_eq!(
+            graph.template_context("file:///t/base.html", &globals),
+            None
+        );
+        assert!(
+            graph
+                .template_context("file:///t/page.html", &globals)
+                .is_some()
+        );
+        assert_eq!(graph.render_calls("file:///t/list.html").len(), 1);
+    }
+
+    #[test]
+    fn names_read_from_backend() {
+        let source = r#"{% extends "base.html" %}
+{% import "forms.html" as forms %}
+{% from "fields.html" import input as field, label %}
+{% macro row(value) %}{{ value }}{% endmacro %}
+{% block content %}
+  {{ forms.input(title) }} {{ field(user.name) }} {{ label() }}
+  {{ row(items) }} {{ content }}
+{% endblock %}"#;
+        let tree = prepare_jinja_tree(source);
+        let query = Queries::default();
+        let definitions = definition_query(
+            &query.jinja_definitions,
+            &tree,
+            Point::default(),
+            source,
+            true,
+        );
+        let names = context_names(&tree, source, &query, &definitions.collect());
+        assert_eq!(names, vec!["title", "user", "items"]);
+    }
+
+    #[test]
+    fn imported_names_with_alias() {
+        let source = r#"{% from "forms.html" import input as field, label %}"#;
+        let tree = prepare_jinja_tree(source);
+        let query = Queries::default();
+        let imports = templates_query(&query.jinja_imports, &tree, Point::default(), source, true);
+        let imports = imports.show();
+        let names = imports[0].imported_names();
+        let names: Vec<(&str, Option<&str>)> = names
+            .iter()
+            .map(|(name, alias)| (name.name.as_str(), alias.map(|alias| alias.name.as_str())))
+            .collect();
+        assert_eq!(names, vec![("input", Some("field")), ("label", None)]);
+    }
+
+    #[test]
+    fn semantic_tokens_are_relative() {
+        let source = r#"{{ name | upper }}{% if name is defined %}{% endif %}"#;
+        let tree = prepare_jinja_tree(source);
+        let query = Queries::default();
+        let objects = objects_query(&query.jinja_objects, &tree, Point::default(), source, true);
+        let tests: Vec<&str> = objects
+            .objects
+            .iter()
+            .filter(|object| object.is_test)
+            .map(|object| object.name.as_str())
+            .collect();
+        assert_eq!(tests, vec!["defined"]);
+
+        let tokens = vec![
+            JinjaToken::new(
+                (Point::new(1, 4), Point::new(1, 8)),
+                SemanticTokenType::VARIABLE,
+            ),
+            JinjaToken::new(
+                (Point::new(0, 3), Point::new(0, 7)),
+                SemanticTokenType::VARIABLE,
+            ),
+            JinjaToken::new(
+                (Point::new(0, 10), Point::new(0, 15)),
+                SemanticTokenType::FUNCTION,
+            ),
+            JinjaToken::new(
+                (Point::new(0, 10), Point::new(0, 15)),
+                SemanticTokenType::VARIABLE,
+            ),
+        ];
+        let encoded = encode_tokens(tokens);
+        let encoded: Vec<(u32, u32, u32, u32)> = encoded
+            .iter()
+            .map(|token| {
+                (
+                    token.delta_line,
+                    token.delta_start,
+                    token.length,
+                    token.token_type,
+                )
+            })
+            .collect();
+        assert_eq!(encoded, vec![(0, 3, 4, 0), (0, 7, 5, 3), (1, 4, 4, 0)]);
+    }
+
+    #[test]
+    fn format_nested_blocks() {
+        let source = "<div>\n{% if user %}\n<p>{{user.name}}</p>\n  <span>\n  </span>\n{%- else %}\n{% for item in items -%}\n{{-   item   +}}\n{% endfor %}\n{% endif %}\n</div>\n{% raw %}\n{{x}}\n{% endraw %}";
+        let tree = prepare_jinja_tree(source);
+        let options = FormatOptions {
+            indent: String::from("  "),
+            ..Default::default()
+        };
+        let formatted = format_template(&tree, source, &options).unwrap();
+        let expected = "<div>\n{% if user %}\n  <p>{{ user.name }}</p>\n    <span>\n    </span>\n{%- else %}\n  {% for item in items -%}\n    {{- item +}}\n  {% endfor %}\n{% endif %}\n</div>\n{% raw %}\n{{x}}\n{% endraw %}";
+        assert_eq!(formatted, expected);
+
+        let source = "{% set x = 1 %}\n{% set y %}\nhi\n{% endset %}\n<b>{{x}}</b>";
+        let tree = prepare_jinja_tree(source);
+        let formatted = format_template(&tree, source, &options).unwrap();
+        let expected = "{% set x = 1 %}\n{% set y %}\n  hi\n{% endset %}\n<b>{{ x }}</b>";
+        assert_eq!(formatted, expected);
+
+        let source = "{{ x }";
+        let tree = prepare_jinja_tree(source);
+        assert!(format_template(&tree, source, &options).is_none());
+        let source = "{% if x %}\n\t{{ a }}\n  {{ b }}\n{% endif %}";
+        let tree = prepare_jinja_tree(source);
+        let options = FormatOptions {
+            indent: String::from("\t"),
+            tab_size: 2,
+            ..Default::default()
+        };
+        let formatted = format_template(&tree, source, &options).unwrap();
+        assert_eq!(formatted, "{% if x %}\n\t{{ a }}\n\t{{ b }}\n{% endif %}");
+    }
+
+    #[test]
+    fn closed_scopes() {
+        let source = r#"{% macro card(title) %}
+    {% if title %}
+        {{ title }}
+    {% else %}
+        empty
+    {% endif %}
+{% endmacro %}
+{% for item in items %}"#;
+        let tree = prepare_jinja_tree(source);
+        let query = Queries::default();
+        let definitions = definition_query(
+            &query.jinja_definitions,
+            &tree,
+            Point::default(),
+            source,
+            true,
+        );
+        let scopes: Vec<(&str, usize, usize)> = definitions
+            .scopes
+            .iter()
+            .map(|scope| (scope.keyword.as_str(), scope.start.row, scope.end.row))
+            .collect();
+        assert_eq!(scopes, vec![("if", 1, 3), ("else", 3, 5), ("macro", 0, 6)]);
+    }
+
+    #[test]
+    fn folding_ranges_of_scopes_and_comments() {
+        let source = r#"{# first line
+   second line #}
+{% macro card(title) %}
+    {% if title %}
+        {{ title }}
+    {% endif %}
+{% endmacro %}
+{% for item in items %}{{ item }}{% endfor %}
+{# one line #}"#;
+        let tree = prepare_jinja_tree(source);
+        let query = Queries::default();
+        let ranges: Vec<(u32, u32, FoldingRangeKind)> = folding_ranges(&tree, source, &query)
+            .into_iter()
+            .filter_map(|range| Some((range.start_line, range.end_line, range.kind?)))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (0, 1, FoldingRangeKind::Comment),
+                (2, 5, FoldingRangeKind::Region),
+                (3, 4, FoldingRangeKind::Region),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_and_unexpected_scopes() {
+        let source = r#"{% endif %}
+{% for item in items %}
+    {% if item %}
+        {{ item }}
+"#;
+        let tree = prepare_jinja_tree(source);
+        let query = Queries::default();
+        let definitions = definition_query(
+            &query.jinja_definitions,
+            &tree,
+            Point::default(),
+            source,
+            true,
+        );
+        let errors: Vec<(String, Point)> = definitions
+            .errors
+            .iter()
+            .map(|error| {
+                let (diagnostic, identifier) = error.diagnostic();
+                (diagnostic.to_string(), identifier.start)
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    String::from("`endif` without opening statement"),
+                    Point::new(0, 3)
+                ),
+                (
+                    String::from("Unclosed `if` statement, expected `endif`"),
+                    Point::new(2, 7)
+                ),
+                (
+                    String::from("Unclosed `for` statement, expected `endfor`"),
+                    Point::new(1, 3)
+                ),
+            ]
+        );
+        let item = definitions
+            .collect()
+            .into_iter()
+            .find(|definition| definition.name == "item");
+        assert_eq!(item.map(|item| item.scope_ends.1), Some(Point::new(4, 0)));
+
+        let source = "{% if a %}\n{% elif b %}\n{% else %}\n";
+        let tree = prepare_jinja_tree(source);
+        let definitions = definition_query(
+            &query.jinja_definitions,
+            &tree,
+            Point::default(),
+            source,
+            true,
+        );
+        let errors: Vec<(String, Point)> = definitions
+            .errors
+            .iter()
+            .map(|error| {
+                let (diagnostic, identifier) = error.diagnostic();
+                (diagnostic.to_string(), identifier.start)
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![(
+                String::from("Unclosed `if` statement, expected `endif`"),
+                Point::new(0, 3)
+            )]
+        );
+    }
+
+    #[test]
+    fn statements_of_same_block() {
+        let source = r#"{% for item in items %}
+    {% if item %}{{ item }}{% elif other %}x{% else %}y{% endif %}
+{% endfor %}"#;
+        let tree = prepare_jinja_tree(source);
+        let query = Queries::default();
+        let definitions = definition_query(
+            &query.jinja_definitions,
+            &tree,
+            Point::default(),
+            source,
+            true,
+        );
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let statements: Vec<(Point, Point)> = root
+            .named_children(&mut cursor)
+            .filter(|node| node.kind() == "statement")
+            .map(|node| (node.start_position(), node.end_position()))
+            .collect();
+        assert_eq!(statements.len(), 6);
+        let matching = matching_statements(&definitions.scopes, &statements, statements[3]);
+        assert_eq!(
+            matching,
+            vec![statements[1], statements[2], statements[3], statements[4]]
+        );
+        let matching = matching_statements(&definitions.scopes, &statements, statements[5]);
+        assert_eq!(matching, vec![statements[0], statements[5]]);
+    }
+
+    #[test]
+    fn fuzzy_symbols() {
+        assert_eq!(fuzzy_score("", "content"), Some(7));
+        assert_eq!(fuzzy_score("cnt", "content"), Some(5));
+        assert!(fuzzy_score("cont", "content") < fuzzy_score("cont", "main_content"));
+        assert!(fuzzy_score("pn", "PROJECT_NAME") < fuzzy_score("pn", "profile_image_name"));
+        assert_eq!(fuzzy_score("tc", "content"), None);
+    }
+
+    #[test]
+    fn macro_call_signature() {
+        let source =
+            r#"{% macro input(name, value="(a, b)", type=none, size=width(2)) %}{% endmacro %}"#;
+        let tree = prepare_jinja_tree(source);
+        let query = Queries::default();
+        let definitions = definition_query(
+            &query.jinja_definitions,
+            &tree,
+            Point::default(),
+            source,
+            true,
+        );
+        let mut names: Vec<Identifier> = definitions
+            .collect()
+            .into_iter()
+            .filter(|variable| variable.identifier_type == IdentifierType::MacroParameter)
+            .collect();
+        names.sort_by_key(|name| name.start);
+        let statement = tree.root_node().child(0).unwrap();
+        let parameters = macro_parameters(statement, source, &names);
+        assert_eq!(
+            parameters,
+            vec![
+                (String::from("name"), None),
+                (String::from("value"), Some(String::from(r#""(a, b)""#))),
+                (String::from("type"), Some(String::from("none"))),
+                (String::from("size"), Some(String::from("width(2)"))),
+            ]
+        );
+
+        let source = r#"{{ forms.input("a, b", f(1, 2), type="text") }}"#;
+        let tree = prepare_jinja_tree(source);
+        let node = tree.root_node().child(0).unwrap();
+        let offset = source.find("type=").unwrap() + 5;
+        assert_eq!(
+            call_context(node, source, offset),
+            Some(CallContext {
+                callee: String::from("forms.input"),
+                argument: 2,
+                keyword: Some(String::from("type")),
+            })
+        );
+        let source = "{{ input(a == b) }}";
+        let tree = prepare_jinja_tree(source);
+        let node = tree.root_node().child(0).unwrap();
+        let context = call_context(node, source, source.find(')').unwrap()).unwrap();
+        assert_eq!((context.argument, context.keyword), (0, None));
+        assert_eq!(call_context(node, source, source.len()), None);
+        let source = "{{ input(a) }}";
+        let tree = prepare_jinja_tree(source);
+        let node = tree.root_node().child(0).unwrap();
+        assert_eq!(
+            call_context(node, source, source.find(')').unwrap() + 1),
+            None
+        );
+        let source = "<p>{{ input(a, ";
+        let tree = prepare_jinja_tree(source);
+        let node = tree
+            .root_node()
+            .child(tree.root_node().child_count() - 1)
+            .unwrap();
+        let context = call_context(node, source, source.len()).unwrap();
+        assert_eq!((context.callee.as_str(), context.argument), ("input", 1));
+    }
+
+    #[test]
+    fn outgoing_calls_skip_unresolved_callee() {
+        let source = r#"{% macro card(user) %}{% endmacro %}
+{% macro page() %}{{ card(1) }}{{ missing(2) }}{{ card(3) }}{% endmacro %}"#;
+        let tree = prepare_jinja_tree(source);
+        let query = Queries::default();
+        let definitions = definition_query(
+            &query.jinja_definitions,
+            &tree,
+            Point::new(0, 0),
+            source,
+            true,
+        )
+        .collect();
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let calls = root
+            .named_children(&mut cursor)
+            .filter(|node| node.kind() == "expression")
+            .flat_map(|node| macro_calls(node, source));
+        let outgoing = calls_by_callee(calls, |call: &MacroCall| {
+            definitions
+                .iter()
+                .find(|definition| {
+                    definition.identifier_type == IdentifierType::MacroName
+                        && definition.name == call.callee
+                })
+                .map(|definition| definition.start)
+        });
+        let outgoing: Vec<(Point, Vec<usize>)> = outgoing
+            .into_iter()
+            .map(|(to, calls)| (to, calls.iter().map(|call| call.start).collect()))
+            .collect();
+        assert_eq!(outgoing, vec![(Point::new(0, 9), vec![58, 87])]);
+    }
+
+    #[test]
+    fn macro_calls_with_arguments() {
+        let source = r#"{{ forms.input(label("Name"), "a, b", type="text") }}"#;
+        let tree = prepare_jinja_tree(source);
+        let calls = macro_calls(tree.root_node().child(0).unwrap(), source);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].callee, "forms.input");
+        assert_eq!(calls[0].start, 3);
+        let arguments: Vec<(&str, Option<&str>)> = calls[0]
+            .arguments
+            .iter()
+            .map(|argument| (argument.text.as_str(), argument.keyword.as_deref()))
+            .collect();
+        assert_eq!(
+            arguments,
+            vec![
+                (r#"label("Name")"#, None),
+                (r#""a, b""#, None),
+                (r#"type="text""#, Some("type")),
+            ]
+        );
+        assert_eq!(&source[calls[0].arguments[1].start..][..3], r#""a,"#);
+        assert_eq!(calls[1].callee, "label");
+        assert_eq!(calls[1].arguments[0].text, "\"Name\"");
+        let calls = |source: &str| {
+            let tree = prepare_jinja_tree(source);
+            macro_calls(tree.root_node().child(0).unwrap(), source)
+        };
+        assert!(calls("{{ input() }}")[0].arguments.is_empty());
+        assert!(calls("{{ input( }}").is_empty());
+    }
+
+    #[test]
+    fn arguments_of_macro_calls() {
+        let parameters = vec![String::from("name"), String::from("value")];
+        let source = r#"{{ input("a", "b", "c", valeu=1, name="d") }}"#;
+        let tree = prepare_jinja_tree(source);
+        let calls = macro_calls(tree.root_node().child(0).unwrap(), source);
+        let errors: Vec<(ArgumentError, &str)> =
+            argument_errors(&calls[0], &parameters, false, false)
+                .into_iter()
+                .map(|(error, argument)| (error, argument.text.as_str()))
+                .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (ArgumentError::TooMany, r#""c""#),
+                (ArgumentError::UnknownKeyword, "valeu=1"),
+            ]
+        );
+        let errors = argument_errors(&calls[0], &parameters, true, false);
+        assert_eq!(errors.len(), 1);
+        assert!(argument_errors(&calls[0], &parameters, true, true).is_empty());
+        let source = "{{ input(*items, 1, 2, 3) }}";
+        let tree = prepare_jinja_tree(source);
+        let calls = macro_calls(tree.root_node().child(0).unwrap(), source);
+        assert!(argument_errors(&calls[0], &parameters, false, false).is_empty());
+    }
+
+    #[test]
+    fn context_of_render_calls() {
+        let source = r#"
+            fn index(jinja: Environment) {
+                let template = jinja.get_template("index.jinja").unwrap();
+                template.render(context! { title, user => "admin" });
+            }
+
+            fn about(jinja: Environment) {
+                jinja.add_global("site", "Title");
+                render_jinja("about.jinja", context! { title => 2 });
+            }
+        "#;
+        let tree = prepare_rust_tree(source);
+        let trigger_point = Point::new(0, 0);
+        let queries = Queries::default();
+        let mut variables = backend_definition_query(
+            &queries.backend_definitions,
+            &tree,
+            trigger_point,
+            source,
+            true,
+        )
+        .show();
+        let mut templates = backend_templates_query(
+            &queries.backend_templates,
+            &tree,
+            trigger_point,
+            source,
+            true,
+        )
+        .collect();
+        variables.append(&mut templates);
+        let context = render_context(&tree, source, &variables);
+        let calls: Vec<(&str, Vec<&str>)> = context
+            .calls
+            .iter()
+            .map(|call| {
+                let keys = call.context.iter().map(|key| key.name.as_str()).collect();
+                (call.template.name.as_str(), keys)
+            })
+            .collect();
+        assert_eq!(
+            calls,
+            vec![
+                ("index.jinja", vec!["title", "user"]),
+                ("about.jinja", vec!["title"])
+            ]
+        );
+        let globals: Vec<&str> = context
+            .globals
+            .iter()
+            .map(|global| global.name.as_str())
+            .collect();
+        assert_eq!(globals, vec!["site"]);
+    }
+
+    #[test]
+    fn template_response_in_python() {
+        let source = r#"
+def index(request):
+    return templates.TemplateResponse(request, "index.jinja", {"user": 1})
+
+def about(request):
+    return templates.TemplateResponse(name="about.jinja", context={})
+"#;
+        let tree = prepare_python_tree(source);
+        let mut queries = Queries::default();
+        queries.update_backend("python");
+        let templates = backend_templates_query(
+            &queries.backend_templates,
+            &tree,
+            Point::default(),
+            source,
+            true,
+        )
+        .collect();
+        let names: Vec<&str> = templates
+            .iter()
+            .map(|template| template.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["index.jinja", "about.jinja"]);
+    }
+
+    #[test]
+    fn insert_missing_context() {
+        let source = r#"
+def index():
+    return render_template("index.jinja", title=1)
+
+def about(request):
+    return templates.TemplateResponse(name="about.jinja", context={"user": 1,})
+"#;
+        let tree = prepare_python_tree(source);
+        let trigger_point = Point::new(0, 0);
+        let mut queries = Queries::default();
+        queries.update_backend("python");
+        let mut variables = backend_definition_query(
+            &queries.backend_definitions,
+            &tree,
+            trigger_point,
+            source,
+            true,
+        )
+        .show();
+        let mut templates = backend_templates_query(
+            &queries.backend_templates,
+            &tree,
+            trigger_point,
+            source,
+            true,
+        )
+        .collect();
+        variables.append(&mut templates);
+        let context = render_context(&tree, source, &variables);
+        let names = vec![String::from("a"), String::from("b")];
+        let insertions: Vec<((Point, Point), String)> = context
+            .calls
+            .iter()
+            .filter_map(|call| context_insertion(&tree, source, call, &names))
+            .collect();
+        assert_eq!(
+            insertions,
+            vec![
+                (
+                    (Point::new(2, 49), Point::new(2, 49)),
+                    String::from(", a=None, b=None")
+                ),
+                (
+                    (Point::new(5, 77), Point::new(5, 77)),
+                    String::from(r#" "a": None, "b": None,"#)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn context_of_empty_and_dynamic_calls() {
+        let source = r#"
+def index():
+    return render_template("index.html")
+
+def about():
+    return render_template("about.html", title=1)
+
+def search(**kwargs):
+    return render_template("search.html", **kwargs)
+"#;
+        let (_, context) = prepare_backend_context("python", source);
+        let dynamic: Vec<bool> = context.calls.iter().map(|call| call.dynamic).collect();
+        assert_eq!(dynamic, vec![false, false, true]);
+        let mut graph = TemplateGraph::default();
+        let resolve = |name: &str| Some(format!("file:///t/{name}"));
+        graph.set_calls("file:///t/views.py", &context.calls, resolve);
+        let globals = HashSet::from([String::from("request")]);
+        // no context is known empty context
+        assert_eq!(
+            graph.template_context("file:///t/index.html", &globals),
+            Some(globals.clone())
+        );
+        assert_eq!(
+            graph.template_context("file:///t/about.html", &globals),
+            Some(HashSet::from([
+                String::from("request"),
+                String::from("title")
+            ]))
+        );
+        assert_eq!(
+            graph.template_context("file:///t/search.html", &globals),
+            None
+        );
+    }
+
+    #[test]
+    fn insert_context_without_keys() {
+        let cases = [
+            (
+                "rust",
+                r#"render_jinja("a.jinja");"#,
+                r#"render_jinja("a.jinja", context! { a => (), b => () });"#,
+            ),
+            (
+                "rust",
+                r#"render_jinja("a.jinja", context! {});"#,
+                r#"render_jinja("a.jinja", context! { a => (), b => () });"#,
+            ),
+            (
+                "rust",
+                r#"fn f() { let t = env.get_template("a.jinja"); t.render(()); }"#,
+                r#"fn f() { let t = env.get_template("a.jinja"); t.render(context! { a => (), b => () }); }"#,
+            ),
+            (
+                "python",
+                r#"render_template("a.jinja")"#,
+                r#"render_template("a.jinja", a=None, b=None)"#,
+            ),
+            (
+                "python",
+                r#"templates.TemplateResponse(request, "a.jinja")"#,
+                r#"templates.TemplateResponse(request, "a.jinja", context={"a": None, "b": None})"#,
+            ),
+            (
+                "python",
+                r#"templates.TemplateResponse(name="a.jinja", context={})"#,
+                r#"templates.TemplateResponse(name="a.jinja", context={"a": None, "b": None})"#,
+            ),
+        ];
+        let used = vec![String::from("a"), String::from("b")];
+        for (lang, source, expected) in cases {
+            let (tree, context) = prepare_backend_context(lang, source);
+            let call = &context.calls[0];
+            assert!(!call.dynamic, "{source}");
+            let names = missing_names(call, &used, &HashSet::new());
+            assert_eq!(names, used);
+            let ((start, end), text) =
+                context_insertion(&tree, source, call, &names).expect("insertion");
+            let edited = format!("{}{text}{}", &source[..start.column], &source[end.column..]);
+            assert_eq!(edited, expected);
+        }
+    }
+
+    #[test]
+    fn unused_context() {
+        let cases = [
+            (
+                "rust",
+                r#"render_jinja("a.jinja", context! { title, user => [1, 2], age => 3 });"#,
+                vec![
+                    r#"render_jinja("a.jinja", context! { title, age => 3 });"#,
+                    r#"render_jinja("a.jinja", context! { title, user => [1, 2] });"#,
+                ],
+            ),
+            (
+                "rust",
+                r#"render_jinja("b.jinja", context! { user, age, });"#,
+                vec![
+                    r#"render_jinja("b.jinja", context! { age, });"#,
+                    r#"render_jinja("b.jinja", context! { user, });"#,
+                ],
+            ),
+            (
+                "python",
+                r#"render_template("c.jinja", title=1, user=2, age=3)"#,
+                vec![
+                    r#"render_template("c.jinja", title=1, age=3)"#,
+                    r#"render_template("c.jinja", title=1, user=2)"#,
+                ],
+            ),
+            (
+                "python",
+                r#"templates.TemplateResponse(name="d.jinja", context={"user": 1, "age": 2,})"#,
+                vec![
+                    r#"templates.TemplateResponse(name="d.jinja", context={"age": 2,})"#,
+                    r#"templates.TemplateResponse(name="d.jinja", context={"user": 1,})"#,
+                ],
+            ),
+            // dynamic context
+            (
+                "rust",
+                r#"render_jinja("e.jinja", context! { user, ..ctx });"#,
+                vec![],
+            ),
+            (
+                "python",
+                r#"render_template("f.jinja", user=1, **kwargs)"#,
+                vec![],
+            ),
+        ];
+        let used = vec![String::from("title")];
+        for (lang, source, expected) in cases {
+            let tree = match lang {
+                "rust" => prepare_rust_tree(source),
+                _ => prepare_python_tree(source),
+            };
+            let trigger_point = Point::new(0, 0);
+            let mut queries = Queries::default();
+            queries.update_backend(lang);
+            let mut variables = backend_definition_query(
+                &queries.backend_definitions,
+                &tree,
+                trigger_point,
+                source,
+                true,
+            )
+            .show();
+            let mut templates = backend_templates_query(
+                &queries.backend_templates,
+                &tree,
+                trigger_point,
+                source,
+                true,
+            )
+            .collect();
+            variables.append(&mut templates);
+            let context = render_context(&tree, source, &variables);
+            let call = &context.calls[0];
+            let edited: Vec<String> = unused_keys(call, &used)
+                .into_iter()
+                .filter_map(|key| context_removal(&tree, key))
+                .map(|(start, end)| format!("{}{}", &source[..start.column], &source[end.column..]))
+                .collect();
+            assert_eq!(edited, expected);
+        }
+    }
+
+    #[test]
+    fn nested_document_symbols() {
+        let source = r#"
+            {% set title = "Page" %}
+            {% macro card(user) %}
+                {% set class = "card" %}
+            {% endmacro %}
+            {% block body %}
+                {% for key, value in items %}
+                    {% with total = 5 %}{{ total }}{% endwith %}
+                {% endfor %}
+            {% endblock %}
+        "#;
+        let tree = prepare_jinja_tree(source);
+        let queries = Queries::default();
+        let variables = definition_query(
+            &queries.jinja_definitions,
+            &tree,
+            Point::new(0, 0),
+            source,
+            true,
+        )
+        .collect();
+        let symbols = nested_symbols(&tree, source, &queries, &variables).unwrap();
+        fn flatten(
+            symbols: &[DocumentSymbol],
+            depth: usize,
+            all: &mut Vec<(usize, String, Option<String>)>,
+        ) {
+            for symbol in symbols {
+                all.push((depth, symbol.name.to_owned(), symbol.detail.to_owned()));
+                flatten(symbol.children.as_deref().unwrap_or(&[]), depth + 1, all);
+            }
+        }
+        let mut all = vec![];
+        flatten(&symbols, 0, &mut all);
+        let with = Some(String::from("with"));
+        let for_loop = Some(String::from("for"));
+        assert_eq!(
+            all,
+            vec![
+                (0, String::from("title"), None),
+                (0, String::from("card"), None),
+                (1, String::from("user"), None),
+                (1, String::from("class"), None),
+                (0, String::from("body"), None),
+                (1, String::from("key, value"), for_loop),
+                (2, String::from("total"), with),
+            ]
+        );
+        let body = &symbols[2];
+        assert_eq!(body.range.start.line, 5);
+        assert_eq!(body.range.end.line, 9);
+    }
+
+    #[test]
+    fn linked_editing_ranges() {
+        let source = r#"
+{% block body %}
+{% for item in items %}{{ item }}{% endfor %}
+{% endblock body %}
+{% block title %}{% endblock other %}
+"#;
+        let tree = prepare_jinja_tree(source);
+        let queries = Queries::default();
+        let definitions = definition_query(
+            &queries.jinja_definitions,
+            &tree,
+            Point::new(0, 0),
+            source,
+            true,
+        );
+        let cases = [
+            // `for` keyword
+            (
+                Point::new(2, 4),
+                Some(vec![
+                    (Point::new(2, 3), Point::new(2, 6)),
+                    (Point::new(2, 39), Point::new(2, 42)),
+                ]),
+            ),
+            // `for` in `endfor`
+            (
+                Point::new(2, 40),
+                Some(vec![
+                    (Point::new(2, 3), Point::new(2, 6)),
+                    (Point::new(2, 39), Point::new(2, 42)),
+                ]),
+            ),
+            // `end` in `endfor`
+            (Point::new(2, 37), None),
+            // block name
+            (
+                Point::new(3, 14),
+                Some(vec![
+                    (Point::new(1, 9), Point::new(1, 13)),
+                    (Point::new(3, 12), Point::new(3, 16)),
+                ]),
+            ),
+            // names are different
+            (Point::new(4, 11), None),
+            // expression
+            (Point::new(2, 28), None),
+        ];
+        for (point, expected) in cases {
+            let ranges = linked_ranges(&tree, source, &definitions.scopes, point);
+            assert_eq!(ranges, expected, "at {point:?}");
+        }
+    }
+
+    #[test]
+    fn unknown_filters_and_tests() {
+        let source = r#"
+            {{ name|captalize }} {{ price|money }} {{ items|monye|first }}
+            {% if count is evn %}{% endif %}{% if user is admin %}{% endif %}
+        "#;
+        let tree = prepare_jinja_tree(source);
+        let queries = Queries::default();
+        let filters = vec![
+            String::from("capitalize"),
+            String::from("first"),
+            String::from("money"),
+        ];
+        let tests = vec![String::from("even"), String::from("admin")];
+        let diagnostics = unknown_filters(&tree, source, &queries, &filters, &tests);
+        let diagnostics: Vec<(String, &str)> = diagnostics
+            .iter()
+            .map(|(diagnostic, identifier)| (diagnostic.to_string(), identifier.name.as_str()))
+            .collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                (
+                    String::from("Unknown filter, did you mean `capitalize`?"),
+                    "captalize"
+                ),
+                (
+                    String::from("Unknown filter, did you mean `money`?"),
+                    "monye"
+                ),
+                (String::from("Unknown test, did you mean `even`?"), "evn"),
+            ]
+        );
+    }
+
+    #[test]
+    fn builtin_filters_and_tests() {
+        let queries = Queries::default();
+        let names =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+        let check = |lang: &str, source: &str| -> Vec<String> {
+            let tree = prepare_jinja_tree(source);
+            let filters = names(builtin_filters(lang));
+            let tests = names(builtin_tests(lang));
+            unknown_filters(&tree, source, &queries, &filters, &tests)
+                .into_iter()
+                .map(|(_, identifier)| identifier.name)
+                .collect()
+        };
+        let common = r#"
+            {{ name|e }} {{ name|d("x") }} {{ prices|sum }} {{ "%s"|format(x) }}
+            {{ x|string }} {% for g in items|groupby("kind") %}{% endfor %}
+            {% if x is divisibleby(3) or x is sameas(y) or x is undefined %}{% endif %}
+        "#;
+        assert!(check("rust", common).is_empty());
+        assert!(check("python", common).is_empty());
+        let jinja2 = r#"
+            {{ html|striptags|truncate(9)|wordcount }} {{ title|center(20) }}
+            {{ size|filesizeformat }} <a {{ attrs|xmlattr }}> {% if f is callable %}{% endif %}
+        "#;
+        assert!(check("python", jinja2).is_empty());
+        assert_eq!(
+            check("rust", jinja2),
+            vec![
+                "striptags",
+                "truncate",
+                "wordcount",
+                "center",
+                "filesizeformat",
+                "xmlattr",
+                "callable"
+            ]
+        );
+        let minijinja =
+            "{{ text|lines }} {{ a|zip(b) }} {% if x is startingwith('a') %}{% endif %}";
+        assert!(check("rust", minijinja).is_empty());
+    }
+
+    #[test]
+    fn syntax_errors_from_tree() {
+        let cases = [
+            ("<p>{{ name </p>", vec![("Unclosed `{{`", Point::new(0, 3))]),
+            (
+                "{% if x %}a{% endif",
+                vec![("Unclosed `{%`", Point::new(0, 11))],
+            ),
+            ("a {% %} b", vec![("Missing keyword", Point::new(0, 4))]),
+            ("{{ 'abc }}", vec![("Unexpected `'`", Point::new(0, 3))]),
+            ("{# comment", vec![("Unclosed `{#`", Point::new(0, 0))]),
+            ("{% if x %}{{ a }}{% endif %}", vec![]),
+        ];
+        for (source, expected) in cases {
+            let tree = prepare_jinja_tree(source);
+            let errors = syntax_errors(&tree, source);
+            let errors: Vec<(String, Point)> = errors
+                .iter()
+                .map(|(diagnostic, identifier)| (diagnostic.to_string(), identifier.start))
+                .collect();
+            let expected: Vec<(String, Point)> = expected
+                .into_iter()
+                .map(|(message, start)| (message.to_string(), start))
+                .collect();
+            assert_eq!(errors, expected);
+        }
+    }
 }
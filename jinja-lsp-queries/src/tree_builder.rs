@@ -1,8 +1,10 @@
 use std::fmt::Display;
 
-use tower_lsp::lsp_types::DiagnosticSeverity;
+use tower_lsp::lsp_types::{
+    DiagnosticRelatedInformation, DiagnosticSeverity, DiagnosticTag, Location, Url,
+};
 
-use crate::search::definition::ScopeError;
+use crate::search::{Identifier, definition::ScopeError, syntax::SyntaxError, to_range};
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
 pub enum LangType {
@@ -17,6 +19,28 @@ pub enum JinjaDiagnostic {
     TemplateNotFound,
     CreateNewTemplate,
     ScopeError(ScopeError),
+    /// Variables used by template that render call doesn't pass.
+    MissingContext(Vec<String>),
+    /// Variable passed by render call that template doesn't use.
+    UnusedContext {
+        warn: bool,
+    },
+    /// Filter that isn't built-in, custom or registered in backend, with
+    /// suggested name.
+    UnknownFilter(Option<String>),
+    /// Test that isn't built-in or registered in backend, with suggested name.
+    UnknownTest(Option<String>),
+    SyntaxError(SyntaxError),
+    /// Positional argument after all parameters of macro are taken.
+    TooManyArguments {
+        callee: String,
+        parameters: usize,
+    },
+    /// Keyword argument that isn't parameter of macro, with suggested name.
+    UnknownArgument(Option<String>),
+    /// Extends, include or import statements that lead back to the same
+    /// template, starting with reported one. Each is `(uri, template name)`.
+    Cycle(Vec<(String, Identifier)>),
 }
 
 impl JinjaDiagnostic {
@@ -26,9 +50,48 @@ impl JinjaDiagnostic {
             JinjaDiagnostic::TemplateNotFound => DiagnosticSeverity::ERROR,
             JinjaDiagnostic::DefinedInMultiplePlaces => DiagnosticSeverity::INFORMATION,
             JinjaDiagnostic::CreateNewTemplate => DiagnosticSeverity::HINT,
+            JinjaDiagnostic::ScopeError(ScopeError::Unclosed(_) | ScopeError::UnexpectedEnd(_)) => {
+                DiagnosticSeverity::ERROR
+            }
             JinjaDiagnostic::ScopeError(_) => DiagnosticSeverity::HINT,
+            JinjaDiagnostic::MissingContext(_) => DiagnosticSeverity::WARNING,
+            JinjaDiagnostic::UnusedContext { warn: true } => DiagnosticSeverity::WARNING,
+            JinjaDiagnostic::UnusedContext { warn: false } => DiagnosticSeverity::HINT,
+            JinjaDiagnostic::UnknownFilter(_) => DiagnosticSeverity::WARNING,
+            JinjaDiagnostic::UnknownTest(_) => DiagnosticSeverity::WARNING,
+            JinjaDiagnostic::SyntaxError(_) => DiagnosticSeverity::ERROR,
+            JinjaDiagnostic::TooManyArguments { .. } => DiagnosticSeverity::ERROR,
+            JinjaDiagnostic::UnknownArgument(_) => DiagnosticSeverity::ERROR,
+            JinjaDiagnostic::Cycle(_) => DiagnosticSeverity::ERROR,
+        }
+    }
+
+    pub fn tags(&self) -> Option<Vec<DiagnosticTag>> {
+        match &self {
+            JinjaDiagnostic::UnusedContext { .. } => Some(vec![DiagnosticTag::UNNECESSARY]),
+            _ => None,
         }
     }
+
+    /// Other statements of the same cycle.
+    pub fn related_information(&self) -> Option<Vec<DiagnosticRelatedInformation>> {
+        let JinjaDiagnostic::Cycle(statements) = &self else {
+            return None;
+        };
+        let related: Vec<DiagnosticRelatedInformation> = statements
+            .iter()
+            .skip(1)
+            .filter_map(|(uri, template)| {
+                let uri = Url::parse(uri).ok()?;
+                let range = to_range((template.start, template.end));
+                Some(DiagnosticRelatedInformation {
+                    location: Location::new(uri, range),
+                    message: format!("Cycle continues with `{}`", template.name),
+                })
+            })
+            .collect();
+        (!related.is_empty()).then_some(related)
+    }
 }
 
 impl Display for JinjaDiagnostic {
@@ -50,7 +113,62 @@ impl Display for JinjaDiagnostic {
                 ScopeError::ElseStatement(_) => {
                     f.write_str("Else statement called before if or elif")
                 }
+                ScopeError::Unclosed(scope) => write!(
+                    f,
+                    "Unclosed `{}` statement, expected `{}`",
+                    scope.keyword,
+                    scope.end_keyword()
+                ),
+                ScopeError::UnexpectedEnd(scope) => {
+                    write!(f, "`{}` without opening statement", scope.keyword)
+                }
+            },
+            JinjaDiagnostic::MissingContext(names) => {
+                f.write_str("Missing variables: ")?;
+                let names: Vec<String> = names.iter().map(|name| format!("`{name}`")).collect();
+                f.write_str(&names.join(", "))
+            }
+            JinjaDiagnostic::UnusedContext { .. } => {
+                f.write_str("Variable is not used by template")
+            }
+            JinjaDiagnostic::UnknownFilter(suggestion) => {
+                f.write_str("Unknown filter")?;
+                did_you_mean(f, suggestion)
+            }
+            JinjaDiagnostic::UnknownTest(suggestion) => {
+                f.write_str("Unknown test")?;
+                did_you_mean(f, suggestion)
+            }
+            JinjaDiagnostic::SyntaxError(syntax_error) => match syntax_error {
+                SyntaxError::Unclosed(delimiter) => write!(f, "Unclosed `{delimiter}`"),
+                SyntaxError::Missing(description) => write!(f, "Missing {description}"),
+                SyntaxError::Unexpected(text) => write!(f, "Unexpected `{text}`"),
             },
+            JinjaDiagnostic::TooManyArguments { callee, parameters } => write!(
+                f,
+                "Too many arguments, `{callee}` takes {parameters} positional arguments"
+            ),
+            JinjaDiagnostic::UnknownArgument(suggestion) => {
+                f.write_str("Unknown keyword argument")?;
+                did_you_mean(f, suggestion)
+            }
+            JinjaDiagnostic::Cycle(statements) => {
+                f.write_str("Cyclic template dependency: ")?;
+                let last = statements.last().map(|(_, template)| template);
+                let names: Vec<String> = last
+                    .into_iter()
+                    .chain(statements.iter().map(|(_, template)| template))
+                    .map(|template| format!("`{}`", template.name))
+                    .collect();
+                f.write_str(&names.join(" -> "))
+            }
         }
     }
 }
+
+fn did_you_mean(f: &mut std::fmt::Formatter<'_>, suggestion: &Option<String>) -> std::fmt::Result {
+    match suggestion {
+        Some(suggestion) => write!(f, ", did you mean `{suggestion}`?"),
+        None => Ok(()),
+    }
+}
@@ -6,9 +6,20 @@ use tokio::sync::{
 use tower_lsp::{
     jsonrpc::Result,
     lsp_types::{
-        CompletionParams, CompletionResponse, DidChangeConfigurationParams,
-        DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentSymbolParams,
-        DocumentSymbolResponse, InitializeParams, InitializeResult, Location, ReferenceParams,
+        CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams, CallHierarchyItem,
+        CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+        CodeLens, CodeLensParams, CompletionParams, CompletionResponse,
+        DidChangeConfigurationParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+        DocumentFormattingParams, DocumentHighlight, DocumentHighlightParams, DocumentLink,
+        DocumentLinkParams, DocumentRangeFormattingParams, DocumentSymbolParams,
+        DocumentSymbolResponse, FoldingRange, FoldingRangeParams, InitializeParams,
+        InitializeResult, InlayHint, InlayHintParams, LinkedEditingRangeParams,
+        LinkedEditingRanges, Location, PrepareRenameResponse, ReferenceParams, RenameFilesParams,
+        RenameParams, SemanticTokensParams, SemanticTokensRangeParams, SemanticTokensRangeResult,
+        SemanticTokensResult, SignatureHelp, SignatureHelpParams, SymbolInformation,
+        TextDocumentPositionParams, TextEdit, TypeHierarchyItem, TypeHierarchyPrepareParams,
+        TypeHierarchySubtypesParams, TypeHierarchySupertypesParams, WorkspaceEdit,
+        WorkspaceSymbolParams,
     },
     Client, LanguageServer,
 };
@@ -153,6 +164,304 @@ impl LanguageServer for _Backend {
         Ok(None)
     }
 
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::PrepareRename(params, sender))
+            .await;
+        if let Ok(response) = tx.await {
+            return Ok(response);
+        }
+        Ok(None)
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::Rename(params, sender))
+            .await;
+        if let Ok(edit) = tx.await {
+            return Ok(edit);
+        }
+        Ok(None)
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::SemanticTokensFull(params, sender))
+            .await;
+        if let Ok(tokens) = tx.await {
+            return Ok(tokens);
+        }
+        Ok(None)
+    }
+
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> Result<Option<SemanticTokensRangeResult>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::SemanticTokensRange(params, sender))
+            .await;
+        if let Ok(tokens) = tx.await {
+            return Ok(tokens);
+        }
+        Ok(None)
+    }
+
+    async fn document_link(&self, params: DocumentLinkParams) -> Result<Option<Vec<DocumentLink>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::DocumentLink(params, sender))
+            .await;
+        if let Ok(links) = tx.await {
+            return Ok(links);
+        }
+        Ok(None)
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::InlayHint(params, sender))
+            .await;
+        if let Ok(hints) = tx.await {
+            return Ok(hints);
+        }
+        Ok(None)
+    }
+
+    async fn linked_editing_range(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> Result<Option<LinkedEditingRanges>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::LinkedEditingRange(params, sender))
+            .await;
+        if let Ok(ranges) = tx.await {
+            return Ok(ranges);
+        }
+        Ok(None)
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::CodeLens(params, sender))
+            .await;
+        if let Ok(lenses) = tx.await {
+            return Ok(lenses);
+        }
+        Ok(None)
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::PrepareCallHierarchy(params, sender))
+            .await;
+        if let Ok(items) = tx.await {
+            return Ok(items);
+        }
+        Ok(None)
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::IncomingCalls(params, sender))
+            .await;
+        if let Ok(calls) = tx.await {
+            return Ok(calls);
+        }
+        Ok(None)
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::OutgoingCalls(params, sender))
+            .await;
+        if let Ok(calls) = tx.await {
+            return Ok(calls);
+        }
+        Ok(None)
+    }
+
+    async fn prepare_type_hierarchy(
+        &self,
+        params: TypeHierarchyPrepareParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::PrepareTypeHierarchy(params, sender))
+            .await;
+        if let Ok(items) = tx.await {
+            return Ok(items);
+        }
+        Ok(None)
+    }
+
+    async fn supertypes(
+        &self,
+        params: TypeHierarchySupertypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::Supertypes(params, sender))
+            .await;
+        if let Ok(items) = tx.await {
+            return Ok(items);
+        }
+        Ok(None)
+    }
+
+    async fn subtypes(
+        &self,
+        params: TypeHierarchySubtypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::Subtypes(params, sender))
+            .await;
+        if let Ok(items) = tx.await {
+            return Ok(items);
+        }
+        Ok(None)
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::SignatureHelp(params, sender))
+            .await;
+        if let Ok(signature) = tx.await {
+            return Ok(signature);
+        }
+        Ok(None)
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::WorkspaceSymbol(params, sender))
+            .await;
+        if let Ok(symbols) = tx.await {
+            return Ok(symbols);
+        }
+        Ok(None)
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::DocumentHighlight(params, sender))
+            .await;
+        if let Ok(highlights) = tx.await {
+            return Ok(highlights);
+        }
+        Ok(None)
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::FoldingRange(params, sender))
+            .await;
+        if let Ok(ranges) = tx.await {
+            return Ok(ranges);
+        }
+        Ok(None)
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::Formatting(params, sender))
+            .await;
+        if let Ok(edits) = tx.await {
+            return Ok(edits);
+        }
+        Ok(None)
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::RangeFormatting(params, sender))
+            .await;
+        if let Ok(edits) = tx.await {
+            return Ok(edits);
+        }
+        Ok(None)
+    }
+
+    async fn will_rename_files(&self, params: RenameFilesParams) -> Result<Option<WorkspaceEdit>> {
+        let (sender, tx) = oneshot::channel();
+        let _ = self
+            .main_channel
+            .send(LspMessage::WillRenameFiles(params, sender))
+            .await;
+        if let Ok(edit) = tx.await {
+            return Ok(edit);
+        }
+        Ok(None)
+    }
+
+    async fn did_rename_files(&self, params: RenameFilesParams) {
+        let _ = self
+            .main_channel
+            .send(LspMessage::DidRenameFiles(params))
+            .await;
+    }
+
     async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
         let _ = self
             .main_channel
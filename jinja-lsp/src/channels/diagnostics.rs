@@ -5,8 +5,8 @@ use jinja_lsp_queries::{
 };
 use tokio::sync::mpsc::{Receiver, Sender};
 use tower_lsp::{
-    lsp_types::{MessageType, Url},
     Client,
+    lsp_types::{MessageType, Url},
 };
 
 use super::lsp::LspMessage;
@@ -33,11 +33,13 @@ pub fn diagnostics_task(
                         code_actions.insert(uri.to_owned(), v);
                         let mut v = vec![];
                         for error in errors {
-                            let diagnostic = create_diagnostic(
+                            let mut diagnostic = create_diagnostic(
                                 &error.1,
                                 error.0.severity(),
                                 error.0.to_string(),
                             );
+                            diagnostic.tags = error.0.tags();
+                            diagnostic.related_information = error.0.related_information();
                             v.push(diagnostic);
                         }
                         let uri = Url::parse(&uri).unwrap();
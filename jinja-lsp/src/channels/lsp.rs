@@ -1,5 +1,5 @@
 use jinja_lsp_queries::search::{
-    Identifier, objects::CompletionType, snippets_completion::snippets, to_range,
+    Identifier, objects::CompletionType, semantic_tokens, snippets_completion::snippets, to_range,
 };
 use serde_json::Value;
 use std::collections::HashMap;
@@ -7,16 +7,33 @@ use tokio::sync::{mpsc, oneshot};
 use tower_lsp::{
     Client,
     lsp_types::{
-        CodeActionParams, CodeActionProviderCapability, CodeActionResponse, CompletionItem,
+        CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams, CallHierarchyItem,
+        CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+        CallHierarchyServerCapability, CodeActionParams, CodeActionProviderCapability,
+        CodeActionResponse, CodeLens, CodeLensOptions, CodeLensParams, CompletionItem,
         CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
         CompletionTextEdit, DidChangeConfigurationParams, DidChangeTextDocumentParams,
-        DidOpenTextDocumentParams, DidSaveTextDocumentParams, DocumentSymbolParams,
-        DocumentSymbolResponse, Documentation, ExecuteCommandOptions, ExecuteCommandParams,
-        GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
-        HoverProviderCapability, InitializeParams, InitializeResult, InsertReplaceEdit, Location,
-        MarkupContent, MarkupKind, MessageType, OneOf, ReferenceParams, ServerCapabilities,
-        ServerInfo, TextDocumentIdentifier, TextDocumentSyncCapability, TextDocumentSyncKind,
-        TextDocumentSyncOptions, TextDocumentSyncSaveOptions, TextEdit,
+        DidOpenTextDocumentParams, DidSaveTextDocumentParams, DocumentFilter,
+        DocumentFormattingParams, DocumentHighlight, DocumentHighlightParams, DocumentLink,
+        DocumentLinkOptions, DocumentLinkParams, DocumentRangeFormattingParams,
+        DocumentSymbolParams, DocumentSymbolResponse, Documentation, ExecuteCommandOptions,
+        ExecuteCommandParams, FileOperationFilter, FileOperationPattern, FileOperationPatternKind,
+        FileOperationRegistrationOptions, FoldingRange, FoldingRangeParams,
+        FoldingRangeProviderCapability, GotoDefinitionParams, GotoDefinitionResponse, Hover,
+        HoverContents, HoverParams, HoverProviderCapability, InitializeParams, InitializeResult,
+        InlayHint, InlayHintParams, InsertReplaceEdit, LinkedEditingRangeParams,
+        LinkedEditingRangeServerCapabilities, LinkedEditingRanges, Location, MarkupContent,
+        MarkupKind, MessageType, OneOf, PrepareRenameResponse, ReferenceParams, Registration,
+        RenameFilesParams, RenameOptions, RenameParams, SemanticTokensFullOptions,
+        SemanticTokensOptions, SemanticTokensParams, SemanticTokensRangeParams,
+        SemanticTokensRangeResult, SemanticTokensResult, ServerCapabilities, ServerInfo,
+        SignatureHelp, SignatureHelpOptions, SignatureHelpParams, SymbolInformation,
+        TextDocumentIdentifier, TextDocumentPositionParams, TextDocumentRegistrationOptions,
+        TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
+        TextDocumentSyncSaveOptions, TextEdit, TypeHierarchyItem, TypeHierarchyPrepareParams,
+        TypeHierarchyRegistrationOptions, TypeHierarchySubtypesParams,
+        TypeHierarchySupertypesParams, WorkspaceEdit, WorkspaceFileOperationsServerCapabilities,
+        WorkspaceServerCapabilities, WorkspaceSymbolParams,
     },
 };
 
@@ -37,9 +54,12 @@ pub fn lsp_task(
 ) {
     let mut config = JinjaConfig::default();
     let mut lsp_data = LspFiles::default();
-    let mut filters = init_filter_completions();
-    let template_tests = init_template_test_completions();
+    let mut filters = init_filter_completions(&config.lang);
+    let mut template_tests = init_template_test_completions(&config.lang);
     let snippets = snippets();
+    // `ServerCapabilities` has no field for type hierarchy, it is registered
+    // after initialization when client allows it.
+    let mut register_type_hierarchy = false;
     tokio::spawn(async move {
         while let Some(msg) = lsp_recv.recv().await {
             match msg {
@@ -61,7 +81,16 @@ pub fn lsp_task(
                             Some(config)
                         })
                         .unwrap_or(search_config().unwrap_or(config));
+                    filters = init_filter_completions(&config.lang);
+                    template_tests = init_template_test_completions(&config.lang);
                     add_custom_filter_completions(&mut filters, &config);
+                    register_type_hierarchy = params
+                        .capabilities
+                        .text_document
+                        .as_ref()
+                        .and_then(|text_document| text_document.type_hierarchy)
+                        .and_then(|type_hierarchy| type_hierarchy.dynamic_registration)
+                        .unwrap_or_default();
 
                     let definition_provider = Some(OneOf::Left(true));
                     let references_provider = Some(OneOf::Left(true));
@@ -72,6 +101,71 @@ pub fn lsp_task(
                         ..Default::default()
                     });
                     let document_symbol_provider = Some(OneOf::Left(true));
+                    let rename_provider = Some(OneOf::Right(RenameOptions {
+                        prepare_provider: Some(true),
+                        work_done_progress_options: Default::default(),
+                    }));
+                    let semantic_tokens_provider = Some(
+                        SemanticTokensOptions {
+                            legend: semantic_tokens::legend(),
+                            range: Some(true),
+                            full: Some(SemanticTokensFullOptions::Bool(true)),
+                            ..Default::default()
+                        }
+                        .into(),
+                    );
+                    let document_link_provider = Some(DocumentLinkOptions {
+                        resolve_provider: Some(false),
+                        work_done_progress_options: Default::default(),
+                    });
+                    let inlay_hint_provider = Some(OneOf::Left(true));
+                    let linked_editing_range_provider =
+                        Some(LinkedEditingRangeServerCapabilities::Simple(true));
+                    let code_lens_provider = Some(CodeLensOptions {
+                        resolve_provider: Some(false),
+                    });
+                    let call_hierarchy_provider = Some(CallHierarchyServerCapability::Simple(true));
+                    let signature_help_provider = Some(SignatureHelpOptions {
+                        trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+                        retrigger_characters: None,
+                        work_done_progress_options: Default::default(),
+                    });
+                    let workspace_symbol_provider = Some(OneOf::Left(true));
+                    let document_highlight_provider = Some(OneOf::Left(true));
+                    let folding_range_provider = Some(FoldingRangeProviderCapability::Simple(true));
+                    let document_formatting_provider = Some(OneOf::Left(true));
+                    let document_range_formatting_provider = Some(OneOf::Left(true));
+                    let file_operations = FileOperationRegistrationOptions {
+                        filters: vec![
+                            FileOperationFilter {
+                                scheme: Some(String::from("file")),
+                                pattern: FileOperationPattern {
+                                    glob: format!(
+                                        "**/*.{{{}}}",
+                                        config.template_extensions.join(",")
+                                    ),
+                                    matches: Some(FileOperationPatternKind::File),
+                                    options: None,
+                                },
+                            },
+                            FileOperationFilter {
+                                scheme: Some(String::from("file")),
+                                pattern: FileOperationPattern {
+                                    glob: String::from("**/*"),
+                                    matches: Some(FileOperationPatternKind::Folder),
+                                    options: None,
+                                },
+                            },
+                        ],
+                    };
+                    let workspace = Some(WorkspaceServerCapabilities {
+                        workspace_folders: None,
+                        file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                            will_rename: Some(file_operations.clone()),
+                            did_rename: Some(file_operations),
+                            ..Default::default()
+                        }),
+                    });
 
                     let msg = InitializeResult {
                         capabilities: ServerCapabilities {
@@ -102,6 +196,20 @@ pub fn lsp_task(
                             execute_command_provider,
                             document_symbol_provider,
                             hover_provider,
+                            rename_provider,
+                            workspace,
+                            semantic_tokens_provider,
+                            document_formatting_provider,
+                            document_range_formatting_provider,
+                            folding_range_provider,
+                            document_highlight_provider,
+                            workspace_symbol_provider,
+                            signature_help_provider,
+                            call_hierarchy_provider,
+                            code_lens_provider,
+                            linked_editing_range_provider,
+                            inlay_hint_provider,
+                            document_link_provider,
                             ..ServerCapabilities::default()
                         },
                         server_info: Some(ServerInfo {
@@ -114,6 +222,29 @@ pub fn lsp_task(
                 }
                 LspMessage::Initialized(sender) => {
                     client.log_message(MessageType::INFO, "Initialized").await;
+                    if register_type_hierarchy {
+                        register_type_hierarchy = false;
+                        let options = TypeHierarchyRegistrationOptions {
+                            text_document_registration_options: TextDocumentRegistrationOptions {
+                                document_selector: Some(vec![DocumentFilter {
+                                    language: None,
+                                    scheme: Some(String::from("file")),
+                                    pattern: Some(format!(
+                                        "**/*.{{{}}}",
+                                        config.template_extensions.join(",")
+                                    )),
+                                }]),
+                            },
+                            type_hierarchy_options: Default::default(),
+                            static_registration_options: Default::default(),
+                        };
+                        let registration = Registration {
+                            id: String::from("type-hierarchy"),
+                            method: String::from("textDocument/prepareTypeHierarchy"),
+                            register_options: serde_json::to_value(options).ok(),
+                        };
+                        let _ = client.register_capability(vec![registration]).await;
+                    }
                     if !config.user_defined {
                         client
                             .log_message(MessageType::WARNING, "Config doesn't exist.")
@@ -268,7 +399,16 @@ pub fn lsp_task(
                         .uri
                         .clone();
                     let mut res = None;
-                    if let Some(hover) = lsp_data.hover(params) {
+                    if let Some(value) = lsp_data.macro_hover(&params) {
+                        let markup_content = MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value,
+                        };
+                        res = Some(Hover {
+                            contents: HoverContents::Markup(markup_content),
+                            range: None,
+                        });
+                    } else if let Some(hover) = lsp_data.hover(params) {
                         if hover.1 == CompletionType::Filter {
                             let filter = filters.iter().find(|name| name.name == hover.0.name);
                             if let Some(filter) = filter {
@@ -344,6 +484,17 @@ pub fn lsp_task(
                         let (sender2, _) = oneshot::channel();
                         let _ = lsp_channel.send(LspMessage::Initialized(sender2)).await;
                         let _ = sender.send(None);
+                    } else if command == "warn" {
+                        // severity is warning unless `false` is passed
+                        let warn = params
+                            .arguments
+                            .first()
+                            .and_then(Value::as_bool)
+                            .unwrap_or(true);
+                        if let Some(errors) = lsp_data.warn(warn) {
+                            let _ = diagnostics_channel.send(errors).await;
+                        }
+                        let _ = sender.send(None);
                     }
                 }
                 LspMessage::DidOpen(params) => {
@@ -356,6 +507,118 @@ pub fn lsp_task(
                         let _ = sender.send(Some(symbols));
                     }
                 }
+                LspMessage::PrepareRename(params, sender) => {
+                    if let Some(response) = lsp_data.prepare_rename(params) {
+                        let _ = sender.send(Some(response));
+                    }
+                }
+                LspMessage::Rename(params, sender) => {
+                    if let Some(edit) = lsp_data.rename(params) {
+                        let _ = sender.send(Some(edit));
+                    }
+                }
+                LspMessage::SemanticTokensFull(params, sender) => {
+                    if let Some(tokens) = lsp_data.semantic_tokens(&params.text_document.uri, None)
+                    {
+                        let _ = sender.send(Some(tokens.into()));
+                    }
+                }
+                LspMessage::SemanticTokensRange(params, sender) => {
+                    let uri = &params.text_document.uri;
+                    if let Some(tokens) = lsp_data.semantic_tokens(uri, Some(params.range)) {
+                        let _ = sender.send(Some(tokens.into()));
+                    }
+                }
+                LspMessage::DocumentLink(params, sender) => {
+                    if let Some(links) = lsp_data.document_link(params) {
+                        let _ = sender.send(Some(links));
+                    }
+                }
+                LspMessage::InlayHint(params, sender) => {
+                    if let Some(hints) = lsp_data.inlay_hint(params) {
+                        let _ = sender.send(Some(hints));
+                    }
+                }
+                LspMessage::LinkedEditingRange(params, sender) => {
+                    if let Some(ranges) = lsp_data.linked_editing_range(params) {
+                        let _ = sender.send(Some(ranges));
+                    }
+                }
+                LspMessage::CodeLens(params, sender) => {
+                    if let Some(lenses) = lsp_data.code_lens(params) {
+                        let _ = sender.send(Some(lenses));
+                    }
+                }
+                LspMessage::PrepareCallHierarchy(params, sender) => {
+                    if let Some(items) = lsp_data.prepare_call_hierarchy(params) {
+                        let _ = sender.send(Some(items));
+                    }
+                }
+                LspMessage::IncomingCalls(params, sender) => {
+                    if let Some(calls) = lsp_data.incoming_calls(params) {
+                        let _ = sender.send(Some(calls));
+                    }
+                }
+                LspMessage::OutgoingCalls(params, sender) => {
+                    if let Some(calls) = lsp_data.outgoing_calls(params) {
+                        let _ = sender.send(Some(calls));
+                    }
+                }
+                LspMessage::PrepareTypeHierarchy(params, sender) => {
+                    if let Some(items) = lsp_data.prepare_type_hierarchy(params) {
+                        let _ = sender.send(Some(items));
+                    }
+                }
+                LspMessage::Supertypes(params, sender) => {
+                    if let Some(items) = lsp_data.supertypes(params) {
+                        let _ = sender.send(Some(items));
+                    }
+                }
+                LspMessage::Subtypes(params, sender) => {
+                    if let Some(items) = lsp_data.subtypes(params) {
+                        let _ = sender.send(Some(items));
+                    }
+                }
+                LspMessage::SignatureHelp(params, sender) => {
+                    if let Some(signature) = lsp_data.signature_help(params) {
+                        let _ = sender.send(Some(signature));
+                    }
+                }
+                LspMessage::WorkspaceSymbol(params, sender) => {
+                    if let Some(symbols) = lsp_data.workspace_symbols(params) {
+                        let _ = sender.send(Some(symbols));
+                    }
+                }
+                LspMessage::DocumentHighlight(params, sender) => {
+                    if let Some(highlights) = lsp_data.document_highlight(params) {
+                        let _ = sender.send(Some(highlights));
+                    }
+                }
+                LspMessage::FoldingRange(params, sender) => {
+                    if let Some(ranges) = lsp_data.folding_ranges(params) {
+                        let _ = sender.send(Some(ranges));
+                    }
+                }
+                LspMessage::Formatting(params, sender) => {
+                    if let Some(edits) = lsp_data.formatting(params) {
+                        let _ = sender.send(Some(edits));
+                    }
+                }
+                LspMessage::RangeFormatting(params, sender) => {
+                    if let Some(edits) = lsp_data.range_formatting(params) {
+                        let _ = sender.send(Some(edits));
+                    }
+                }
+                LspMessage::WillRenameFiles(params, sender) => {
+                    if let Some(edit) = lsp_data.will_rename_files(params) {
+                        let _ = sender.send(Some(edit));
+                    }
+                }
+                LspMessage::DidRenameFiles(params) => {
+                    if let Some(errors) = lsp_data.did_rename_files(params) {
+                        let _ = diagnostics_channel.send(errors).await;
+                    }
+                }
                 LspMessage::DidChangeConfiguration(params) => {
                     let (sender, _) = oneshot::channel();
                     if let Ok(c) = serde_json::from_value(params.settings) {
@@ -403,4 +666,74 @@ pub enum LspMessage {
     DidChangeConfiguration(DidChangeConfigurationParams),
     CodeActions(HashMap<String, Vec<Identifier>>),
     GoToReferences(ReferenceParams, oneshot::Sender<Option<Vec<Location>>>),
+    PrepareRename(
+        TextDocumentPositionParams,
+        oneshot::Sender<Option<PrepareRenameResponse>>,
+    ),
+    Rename(RenameParams, oneshot::Sender<Option<WorkspaceEdit>>),
+    WillRenameFiles(RenameFilesParams, oneshot::Sender<Option<WorkspaceEdit>>),
+    DidRenameFiles(RenameFilesParams),
+    SemanticTokensFull(
+        SemanticTokensParams,
+        oneshot::Sender<Option<SemanticTokensResult>>,
+    ),
+    SemanticTokensRange(
+        SemanticTokensRangeParams,
+        oneshot::Sender<Option<SemanticTokensRangeResult>>,
+    ),
+    FoldingRange(
+        FoldingRangeParams,
+        oneshot::Sender<Option<Vec<FoldingRange>>>,
+    ),
+    Formatting(
+        DocumentFormattingParams,
+        oneshot::Sender<Option<Vec<TextEdit>>>,
+    ),
+    RangeFormatting(
+        DocumentRangeFormattingParams,
+        oneshot::Sender<Option<Vec<TextEdit>>>,
+    ),
+    DocumentHighlight(
+        DocumentHighlightParams,
+        oneshot::Sender<Option<Vec<DocumentHighlight>>>,
+    ),
+    WorkspaceSymbol(
+        WorkspaceSymbolParams,
+        oneshot::Sender<Option<Vec<SymbolInformation>>>,
+    ),
+    SignatureHelp(SignatureHelpParams, oneshot::Sender<Option<SignatureHelp>>),
+    DocumentLink(
+        DocumentLinkParams,
+        oneshot::Sender<Option<Vec<DocumentLink>>>,
+    ),
+    InlayHint(InlayHintParams, oneshot::Sender<Option<Vec<InlayHint>>>),
+    LinkedEditingRange(
+        LinkedEditingRangeParams,
+        oneshot::Sender<Option<LinkedEditingRanges>>,
+    ),
+    CodeLens(CodeLensParams, oneshot::Sender<Option<Vec<CodeLens>>>),
+    PrepareCallHierarchy(
+        CallHierarchyPrepareParams,
+        oneshot::Sender<Option<Vec<CallHierarchyItem>>>,
+    ),
+    IncomingCalls(
+        CallHierarchyIncomingCallsParams,
+        oneshot::Sender<Option<Vec<CallHierarchyIncomingCall>>>,
+    ),
+    OutgoingCalls(
+        CallHierarchyOutgoingCallsParams,
+        oneshot::Sender<Option<Vec<CallHierarchyOutgoingCall>>>,
+    ),
+    PrepareTypeHierarchy(
+        TypeHierarchyPrepareParams,
+        oneshot::Sender<Option<Vec<TypeHierarchyItem>>>,
+    ),
+    Supertypes(
+        TypeHierarchySupertypesParams,
+        oneshot::Sender<Option<Vec<TypeHierarchyItem>>>,
+    ),
+    Subtypes(
+        TypeHierarchySubtypesParams,
+        oneshot::Sender<Option<Vec<TypeHierarchyItem>>>,
+    ),
 }
@@ -12,7 +12,7 @@ use jinja_lsp_queries::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::lsp_files::LspFiles;
+use crate::{filter::add_custom_filter_completions, lsp_files::LspFiles};
 use clap::Parser;
 
 /// Jinja configuration
@@ -25,8 +25,15 @@ pub struct JinjaConfig {
     #[serde(skip)]
     pub user_defined: bool,
     pub hide_undefined: Option<bool>,
+    /// Context variables that templates don't use are warnings, hints when
+    /// missing.
+    pub warn_unused: Option<bool>,
     pub template_extensions: Vec<String>,
     pub filters: Vec<String>,
+    /// Indent width used by formatter, editor's tab size is used when missing.
+    pub indent_width: Option<usize>,
+    /// Formatter writes `{{ x }}` instead of `{{x}}`.
+    pub delimiter_padding: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -38,8 +45,11 @@ pub struct OptionalJinjaConfig {
     #[serde(skip)]
     pub user_defined: Option<bool>,
     pub hide_undefined: Option<Option<bool>>,
+    pub warn_unused: Option<Option<bool>>,
     pub template_extensions: Option<Vec<String>>,
     pub filters: Option<Vec<String>>,
+    pub indent_width: Option<Option<usize>>,
+    pub delimiter_padding: Option<Option<bool>>,
 }
 
 impl Default for JinjaConfig {
@@ -50,8 +60,11 @@ impl Default for JinjaConfig {
             lang: "python".to_string(),
             user_defined: false,
             hide_undefined: Some(false),
+            warn_unused: Some(false),
             template_extensions: vec!["html".to_string(), "jinja".to_string(), "j2".to_string()],
             filters: vec![],
+            indent_width: None,
+            delimiter_padding: Some(true),
         }
     }
 }
@@ -71,6 +84,9 @@ impl From<OptionalJinjaConfig> for JinjaConfig {
         if let Some(hide_undefined) = value.hide_undefined {
             config.hide_undefined = hide_undefined;
         }
+        if let Some(warn_unused) = value.warn_unused {
+            config.warn_unused = warn_unused;
+        }
 
         if let Some(new_extensions) = value.template_extensions {
             new_template_extensions(&mut config, Some(new_extensions));
@@ -80,6 +96,14 @@ impl From<OptionalJinjaConfig> for JinjaConfig {
             config.filters = custom_filters;
         }
 
+        if let Some(indent_width) = value.indent_width {
+            config.indent_width = indent_width;
+        }
+
+        if let Some(delimiter_padding) = value.delimiter_padding {
+            config.delimiter_padding = delimiter_padding;
+        }
+
         if let Some(user_defined) = value.user_defined {
             config.user_defined = user_defined;
         }
@@ -172,6 +196,10 @@ pub fn walkdir(config: &JinjaConfig) -> anyhow::Result<InitLsp> {
     let mut lsp_files = LspFiles::default();
     lsp_files.config = config.clone();
     lsp_files.ignore_globals = config.hide_undefined.unwrap_or(false);
+    lsp_files.warn_unused = config.warn_unused.unwrap_or(false);
+    let mut filters = vec![];
+    add_custom_filter_completions(&mut filters, config);
+    lsp_files.filters = filters.into_iter().map(|filter| filter.name).collect();
     if config.lang == "python" {
         lsp_files.queries.update_backend(&config.lang);
         lsp_files.parsers.update_backend(&config.lang);
@@ -1,6 +1,7 @@
 use std::fs;
 
 use ignore::Walk;
+use jinja_lsp_queries::builtins::builtin_filters;
 use serde::{Deserialize, Serialize};
 
 use crate::config::JinjaConfig;
@@ -20,48 +21,71 @@ impl From<(&str, &str)> for FilterCompletion {
     }
 }
 
-pub fn init_filter_completions() -> Vec<FilterCompletion> {
-    vec![
-        FilterCompletion::from(("abs", include_str!("md/filters/abs.md"))),
-        FilterCompletion::from(("attr", include_str!("md/filters/attr.md"))),
-        FilterCompletion::from(("batch", include_str!("md/filters/batch.md"))),
-        FilterCompletion::from(("bool", include_str!("md/filters/bool.md"))),
-        FilterCompletion::from(("capitalize", include_str!("md/filters/capitalize.md"))),
-        FilterCompletion::from(("default", include_str!("md/filters/default.md"))),
-        FilterCompletion::from(("dictsort", include_str!("md/filters/dictsort.md"))),
-        FilterCompletion::from(("escape", include_str!("md/filters/escape.md"))),
-        FilterCompletion::from(("first", include_str!("md/filters/first.md"))),
-        FilterCompletion::from(("float", include_str!("md/filters/float.md"))),
-        FilterCompletion::from(("indent", include_str!("md/filters/indent.md"))),
-        FilterCompletion::from(("int", include_str!("md/filters/int.md"))),
-        FilterCompletion::from(("items", include_str!("md/filters/items.md"))),
-        FilterCompletion::from(("join", include_str!("md/filters/join.md"))),
-        FilterCompletion::from(("last", include_str!("md/filters/last.md"))),
-        FilterCompletion::from(("length", include_str!("md/filters/length.md"))),
-        FilterCompletion::from(("list", include_str!("md/filters/list.md"))),
-        FilterCompletion::from(("lower", include_str!("md/filters/lower.md"))),
-        FilterCompletion::from(("map", include_str!("md/filters/map.md"))),
-        FilterCompletion::from(("max", include_str!("md/filters/max.md"))),
-        FilterCompletion::from(("min", include_str!("md/filters/min.md"))),
-        FilterCompletion::from(("pprint", include_str!("md/filters/pprint.md"))),
-        FilterCompletion::from(("rejectattr", include_str!("md/filters/rejectattr.md"))),
-        FilterCompletion::from(("reject", include_str!("md/filters/reject.md"))),
-        FilterCompletion::from(("replace", include_str!("md/filters/replace.md"))),
-        FilterCompletion::from(("reverse", include_str!("md/filters/reverse.md"))),
-        FilterCompletion::from(("round", include_str!("md/filters/round.md"))),
-        FilterCompletion::from(("safe", include_str!("md/filters/safe.md"))),
-        FilterCompletion::from(("selectattr", include_str!("md/filters/selectattr.md"))),
-        FilterCompletion::from(("select", include_str!("md/filters/select.md"))),
-        FilterCompletion::from(("slice", include_str!("md/filters/slice.md"))),
-        FilterCompletion::from(("sort", include_str!("md/filters/sort.md"))),
-        FilterCompletion::from(("title", include_str!("md/filters/title.md"))),
-        FilterCompletion::from(("tojson", include_str!("md/filters/tojson.md"))),
-        FilterCompletion::from(("trim", include_str!("md/filters/trim.md"))),
-        FilterCompletion::from(("unique", include_str!("md/filters/unique.md"))),
-        FilterCompletion::from(("upper", include_str!("md/filters/upper.md"))),
-        FilterCompletion::from(("urlencode", include_str!("md/filters/urlencode.md"))),
-    ]
+/// Documentation of built-in filters, aliases share it with their filter.
+static FILTER_DOCS: &[(&str, &str)] = &[
+    ("abs", include_str!("md/filters/abs.md")),
+    ("attr", include_str!("md/filters/attr.md")),
+    ("batch", include_str!("md/filters/batch.md")),
+    ("bool", include_str!("md/filters/bool.md")),
+    ("capitalize", include_str!("md/filters/capitalize.md")),
+    ("d", include_str!("md/filters/default.md")),
+    ("default", include_str!("md/filters/default.md")),
+    ("dictsort", include_str!("md/filters/dictsort.md")),
+    ("e", include_str!("md/filters/escape.md")),
+    ("escape", include_str!("md/filters/escape.md")),
+    ("first", include_str!("md/filters/first.md")),
+    ("float", include_str!("md/filters/float.md")),
+    ("indent", include_str!("md/filters/indent.md")),
+    ("int", include_str!("md/filters/int.md")),
+    ("items", include_str!("md/filters/items.md")),
+    ("join", include_str!("md/filters/join.md")),
+    ("last", include_str!("md/filters/last.md")),
+    ("length", include_str!("md/filters/length.md")),
+    ("list", include_str!("md/filters/list.md")),
+    ("lower", include_str!("md/filters/lower.md")),
+    ("map", include_str!("md/filters/map.md")),
+    ("max", include_str!("md/filters/max.md")),
+    ("min", include_str!("md/filters/min.md")),
+    ("pprint", include_str!("md/filters/pprint.md")),
+    ("reject", include_str!("md/filters/reject.md")),
+    ("rejectattr", include_str!("md/filters/rejectattr.md")),
+    ("replace", include_str!("md/filters/replace.md")),
+    ("reverse", include_str!("md/filters/reverse.md")),
+    ("round", include_str!("md/filters/round.md")),
+    ("safe", include_str!("md/filters/safe.md")),
+    ("select", include_str!("md/filters/select.md")),
+    ("selectattr", include_str!("md/filters/selectattr.md")),
+    ("slice", include_str!("md/filters/slice.md")),
+    ("sort", include_str!("md/filters/sort.md")),
+    ("title", include_str!("md/filters/title.md")),
+    ("tojson", include_str!("md/filters/tojson.md")),
+    ("trim", include_str!("md/filters/trim.md")),
+    ("unique", include_str!("md/filters/unique.md")),
+    ("upper", include_str!("md/filters/upper.md")),
+    ("urlencode", include_str!("md/filters/urlencode.md")),
+];
+
+/// Built-in filters of template engine that backend `lang` uses, the same
+/// ones that templates are checked against.
+pub fn init_filter_completions(lang: &str) -> Vec<FilterCompletion> {
+    builtin_filters(lang)
+        .iter()
+        .map(|name| {
+            let desc = FILTER_DOCS
+                .iter()
+                .find(|(filter, _)| filter == name)
+                .map_or_else(
+                    || format!("Built-in filter `{name}`."),
+                    |(_, desc)| desc.to_string(),
+                );
+            FilterCompletion {
+                name: name.to_string(),
+                desc,
+            }
+        })
+        .collect()
 }
+
 pub fn add_custom_filter_completions(filters: &mut Vec<FilterCompletion>, config: &JinjaConfig) {
     for directory in &config.filters {
         let walk = Walk::new(directory);
@@ -1,19 +1,41 @@
 use jinja_lsp_queries::search::Identifier;
 use jinja_lsp_queries::search::IdentifierType;
-use jinja_lsp_queries::search::definition::ScopeError;
 use jinja_lsp_queries::search::definition::definition_query;
+use jinja_lsp_queries::search::definition::{Scope, ScopeError};
 use jinja_lsp_queries::search::objects::CompletionType;
 use jinja_lsp_queries::search::objects::JinjaObject;
 use jinja_lsp_queries::search::objects::objects_query;
+use jinja_lsp_queries::search::resolve::{
+    definition_occurrences, import_occurrences, imported_name_at, is_valid_identifier,
+    linked_ranges, macro_callee, matching_statements, module_occurrences, object_at,
+    resolve_definition, statement_keywords, unresolved_occurrences,
+};
+use jinja_lsp_queries::search::semantic_tokens::{JinjaToken, encode_tokens};
+use jinja_lsp_queries::search::signature::{
+    ArgumentError, MacroCall, Parameter, argument_errors, call_context, calls_by_callee,
+    macro_calls, macro_parameters,
+};
+use jinja_lsp_queries::search::templates::{Import, imported_macro_name};
 use jinja_lsp_queries::{
-    lsp_helper::{path_items, search_errors},
+    builtins::{builtin_filters, builtin_tests},
+    formatter::{FormatOptions, format_template},
+    lsp_helper::{
+        closest_name, context_names, dependent_lenses, document_links, folding_ranges,
+        nested_symbols, path_items, renamed_template, search_errors, template_dependents,
+        template_name, template_path_range, unknown_filters,
+    },
     search::{
-        completion_start,
+        completion_start, fuzzy_score,
         python_identifiers::{PythonIdentifier, python_identifiers},
         queries::Queries,
+        render_context::{
+            BackendContext, RenderCall, context_insertion, context_removal, missing_names,
+            render_context, unused_keys,
+        },
         rust_identifiers::backend_definition_query,
         rust_template_completion::backend_templates_query,
         snippets_completion::snippets_query,
+        template_graph::TemplateGraph,
         templates::templates_query,
         to_point, to_range,
     },
@@ -21,17 +43,28 @@ use jinja_lsp_queries::{
     tree_builder::{JinjaDiagnostic, LangType},
 };
 use std::{
+    cmp::Reverse,
     collections::{HashMap, HashSet},
     fs::read_to_string,
-    path::Path,
+    path::{Path, PathBuf},
     time::Duration,
 };
 use tokio::{sync::mpsc, task::JoinHandle, time::sleep};
 use tower_lsp::lsp_types::{
-    CodeAction, CodeActionKind, CodeActionOrCommand, Command, CompletionItemKind,
-    CompletionTextEdit, CreateFile, CreateFileOptions, DidOpenTextDocumentParams,
-    DocumentChangeOperation, DocumentChanges, DocumentSymbol, DocumentSymbolResponse,
-    InsertReplaceEdit, PartialResultParams, ResourceOp, TextDocumentIdentifier, TextEdit,
+    CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams, CallHierarchyItem,
+    CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeLens, CodeLensParams, Command,
+    CompletionItemKind, CompletionTextEdit, CreateFile, CreateFileOptions,
+    DidOpenTextDocumentParams, DocumentChangeOperation, DocumentChanges, DocumentFormattingParams,
+    DocumentHighlight, DocumentHighlightKind, DocumentHighlightParams, DocumentLink,
+    DocumentLinkParams, DocumentRangeFormattingParams, DocumentSymbol, DocumentSymbolResponse,
+    FoldingRange, FoldingRangeParams, FormattingOptions, InlayHint, InlayHintKind, InlayHintLabel,
+    InlayHintParams, InsertReplaceEdit, LinkedEditingRangeParams, LinkedEditingRanges, OneOf,
+    OptionalVersionedTextDocumentIdentifier, PartialResultParams, PrepareRenameResponse,
+    RenameFile, RenameFileOptions, RenameFilesParams, RenameParams, ResourceOp,
+    SemanticTokenModifier, SemanticTokenType, SemanticTokens, SymbolKind, TextDocumentEdit,
+    TextDocumentIdentifier, TextDocumentPositionParams, TextEdit, TypeHierarchyItem,
+    TypeHierarchyPrepareParams, TypeHierarchySubtypesParams, TypeHierarchySupertypesParams,
     WorkDoneProgressParams, WorkspaceEdit,
 };
 
@@ -44,7 +77,9 @@ use ropey::Rope;
 use tower_lsp::lsp_types::{
     CodeActionParams, CompletionContext, CompletionItem, CompletionParams, CompletionTriggerKind,
     DidChangeTextDocumentParams, DidSaveTextDocumentParams, GotoDefinitionParams,
-    GotoDefinitionResponse, HoverParams, Location, Position, Range, Url,
+    GotoDefinitionResponse, HoverParams, Location, ParameterInformation, ParameterLabel, Position,
+    Range, SignatureHelp, SignatureHelpParams, SignatureInformation, SymbolInformation, Url,
+    WorkspaceSymbolParams,
 };
 use tree_sitter::{InputEdit, Point, Tree};
 
@@ -66,6 +101,16 @@ pub struct LspFiles {
     pub code_actions: HashMap<String, Vec<Identifier>>,
     pub is_vscode: bool,
     pub ignore_globals: bool,
+    /// Unused context variables are warnings instead of hints.
+    pub warn_unused: bool,
+    /// Names of custom filters from config.
+    pub filters: Vec<String>,
+    /// Statements and render calls that link files, updated with variables.
+    graph: TemplateGraph,
+    /// Render calls and globals of backend files, updated with variables.
+    contexts: HashMap<String, BackendContext>,
+    /// Names that template reads from backend, updated with variables.
+    context_names: HashMap<String, Vec<String>>,
 }
 
 impl LspFiles {
@@ -114,12 +159,6 @@ impl LspFiles {
             }
             LangType::Template => {
                 let mut variables = vec![];
-                let query_snippets = &self.queries.jinja_snippets;
-                let snippets =
-                    snippets_query(query_snippets, tree, trigger_point, file_content, true);
-                if snippets.is_error {
-                    return None;
-                }
                 let query_defs = &self.queries.jinja_definitions;
                 let definitions =
                     definition_query(query_defs, tree, trigger_point, file_content, true);
@@ -131,6 +170,30 @@ impl LspFiles {
                 self.scope_errors.insert(String::from(name), scope_errors);
             }
         }
+        self.link_file(name, lang_type, file_content)
+    }
+
+    /// Updates statements or render calls of file `name` in template graph.
+    fn link_file(&mut self, name: &str, lang_type: LangType, file_content: &str) -> Option<()> {
+        let tree = self.trees.get(&lang_type)?.get(name)?;
+        let templates = &self.config.templates;
+        let resolve = |name: &str| template_file_uri(templates, name);
+        match lang_type {
+            LangType::Backend => {
+                let variables = self.variables.get(name)?;
+                let context = render_context(tree, file_content, variables);
+                self.graph.set_calls(name, &context.calls, resolve);
+                self.contexts.insert(name.to_owned(), context);
+            }
+            LangType::Template => {
+                let query = &self.queries.jinja_imports;
+                let imports = templates_query(query, tree, Point::default(), file_content, true);
+                self.graph.set_imports(name, &imports.show(), resolve);
+                let variables = self.variables.get(name)?;
+                let names = context_names(tree, file_content, &self.queries, variables);
+                self.context_names.insert(name.to_owned(), names);
+            }
+        }
         Some(())
     }
 
@@ -227,7 +290,11 @@ impl LspFiles {
         let lang_type = self.config.file_ext(&Path::new(name))?;
         let trees = self.trees.get(&lang_type)?;
         let tree = trees.get(name)?;
-        search_errors(
+        let context = match lang_type {
+            LangType::Template => self.template_context(name),
+            LangType::Backend => None,
+        };
+        let mut diagnostics = search_errors(
             tree,
             &writter.content,
             &self.queries,
@@ -236,7 +303,23 @@ impl LspFiles {
             self.config.templates.clone(),
             lang_type,
             self.ignore_globals,
-        )
+            context.as_ref(),
+        )?;
+        if lang_type == LangType::Backend {
+            for (call, names) in self.missing_context(name) {
+                diagnostics.push((JinjaDiagnostic::MissingContext(names), call.template));
+            }
+            for key in self.unused_context(name) {
+                let warn = self.warn_unused;
+                diagnostics.push((JinjaDiagnostic::UnusedContext { warn }, key));
+            }
+        }
+        if lang_type == LangType::Template {
+            diagnostics.extend(self.filter_errors(name));
+            diagnostics.extend(self.argument_errors(name));
+            diagnostics.extend(self.graph.cycles(name));
+        }
+        Some(diagnostics)
     }
 
     pub fn did_save(&mut self, params: DidSaveTextDocumentParams) -> Option<DiagnosticMessage> {
@@ -246,32 +329,145 @@ impl LspFiles {
         let doc = self.documents.get(uri)?;
         let mut content = FileContent::default();
         let _ = doc.write_to(&mut content);
+        // files linked to old and to new version of `uri`
+        let mut files = self.dependent_files(uri, lang_type);
+        let registered = self.contexts.get(uri).map(registered_names);
         self.delete_variables(uri);
         self.add_variables(uri, lang_type, &content.content);
+        files.extend(self.dependent_files(uri, lang_type));
+        // globals, filters and tests of backend are used by every file
+        if registered != self.contexts.get(uri).map(registered_names) {
+            files.extend(self.documents.keys().cloned());
+        }
+        files.remove(uri);
         let mut hm = HashMap::new();
-        let v = self.read_tree(uri);
-        if let Some(mut v) = v {
-            let scope_errors = self
-                .scope_errors
-                .get(&uri.to_string())
-                .map(|i| {
-                    let mut errors = vec![];
-                    for error in i {
-                        errors.push(error.diagnostic());
-                    }
-                    errors
-                })
-                .unwrap_or(vec![]);
-            v.extend(scope_errors);
-
-            hm.insert(uri.to_owned(), v);
-        } else {
-            hm.insert(uri.to_owned(), vec![]);
+        hm.insert(uri.to_owned(), self.file_diagnostics(uri));
+        for file in files {
+            hm.insert(file.to_owned(), self.file_diagnostics(&file));
         }
         let message = DiagnosticMessage::Errors(hm);
         Some(message)
     }
 
+    /// Files with diagnostics that depend on file `uri`: templates linked to
+    /// it and backend files that render it, or templates that backend file
+    /// renders.
+    fn dependent_files(&self, uri: &str, lang_type: LangType) -> HashSet<String> {
+        let mut files = HashSet::new();
+        match lang_type {
+            LangType::Template => {
+                files.extend(self.graph.linked_templates(uri));
+                for template in self.graph.rendering_templates(uri) {
+                    for (file, _) in self.graph.render_calls(&template) {
+                        files.insert(file.to_owned());
+                    }
+                }
+            }
+            LangType::Backend => {
+                for (template, _) in self.graph.calls_of(uri) {
+                    files.extend(self.graph.rendered_templates(template));
+                }
+            }
+        }
+        files
+    }
+
+    /// Sets severity of unused context variables (warning or hint) and checks
+    /// backend files again.
+    pub fn warn(&mut self, warn: bool) -> Option<DiagnosticMessage> {
+        self.warn_unused = warn;
+        let mut hm = HashMap::new();
+        for file in self.trees.get(&LangType::Backend)?.keys() {
+            hm.insert(file.to_owned(), self.file_diagnostics(file));
+        }
+        Some(DiagnosticMessage::Errors(hm))
+    }
+
+    /// Unknown filters and tests in template `uri`.
+    fn filter_errors(&self, uri: &str) -> Vec<(JinjaDiagnostic, Identifier)> {
+        let (Some(tree), Some(document)) = (
+            self.trees
+                .get(&LangType::Template)
+                .and_then(|trees| trees.get(uri)),
+            self.documents.get(uri),
+        ) else {
+            return vec![];
+        };
+        let lang = &self.config.lang;
+        let mut filters: Vec<String> = builtin_filters(lang)
+            .iter()
+            .map(|filter| filter.to_string())
+            .collect();
+        let mut tests: Vec<String> = builtin_tests(lang)
+            .iter()
+            .map(|test| test.to_string())
+            .collect();
+        filters.extend(self.filters.iter().cloned());
+        for context in self.contexts.values() {
+            filters.extend(context.filters.iter().map(|filter| filter.name.to_owned()));
+            tests.extend(context.tests.iter().map(|test| test.name.to_owned()));
+        }
+        let mut writter = FileContent::default();
+        let _ = document.write_to(&mut writter);
+        unknown_filters(tree, &writter.content, &self.queries, &filters, &tests)
+    }
+
+    /// Arguments of macro calls in template `uri` that called macros don't
+    /// accept.
+    fn argument_errors(&self, uri: &str) -> Vec<(JinjaDiagnostic, Identifier)> {
+        let mut diagnostics = vec![];
+        let Some(rope) = self.documents.get(uri) else {
+            return diagnostics;
+        };
+        let mut signatures = HashMap::new();
+        for call in self.template_calls(uri) {
+            let Some((macro_uri, definition)) = self.macro_definition(uri, &call.callee) else {
+                continue;
+            };
+            let signature = signatures
+                .entry((macro_uri.to_owned(), definition.start))
+                .or_insert_with(|| {
+                    let parameters = self.macro_parameter_names(&macro_uri, &definition);
+                    let (varargs, kwargs) = self.macro_extra_arguments(&macro_uri, &definition);
+                    parameters.map(|parameters| (parameters, varargs, kwargs))
+                });
+            let Some((parameters, varargs, kwargs)) = signature else {
+                continue;
+            };
+            for (error, argument) in argument_errors(&call, parameters, *varargs, *kwargs) {
+                let text = argument.keyword.as_ref().unwrap_or(&argument.text);
+                let (Some(start), Some(end)) = (
+                    byte_point(rope, argument.start),
+                    byte_point(rope, argument.start + text.len()),
+                ) else {
+                    continue;
+                };
+                let diagnostic = match error {
+                    ArgumentError::TooMany => JinjaDiagnostic::TooManyArguments {
+                        callee: call.callee.to_owned(),
+                        parameters: parameters.len(),
+                    },
+                    ArgumentError::UnknownKeyword => {
+                        JinjaDiagnostic::UnknownArgument(closest_name(text, parameters))
+                    }
+                };
+                diagnostics.push((diagnostic, Identifier::new(text, start, end)));
+            }
+        }
+        diagnostics
+    }
+
+    /// Errors from `read_tree` together with scope errors of file `uri`.
+    fn file_diagnostics(&self, uri: &str) -> Vec<(JinjaDiagnostic, Identifier)> {
+        let Some(mut diagnostics) = self.read_tree(uri) else {
+            return vec![];
+        };
+        if let Some(errors) = self.scope_errors.get(uri) {
+            diagnostics.extend(errors.iter().map(|error| error.diagnostic()));
+        }
+        diagnostics
+    }
+
     pub fn completion(&self, params: CompletionParams) -> Option<CompletionType> {
         let can_complete = {
             matches!(
@@ -419,6 +615,16 @@ impl LspFiles {
 
         match lang_type {
             LangType::Template => {
+                if let Some(location) = self.parent_block(&uri, point) {
+                    return Some(GotoDefinitionResponse::Scalar(location));
+                }
+                if let Some((macro_uri, definition)) = self.macro_at(&uri, point)
+                    && macro_uri != uri
+                {
+                    let url = Url::parse(&macro_uri).ok()?;
+                    let range = to_range((definition.start, definition.end));
+                    return Some(GotoDefinitionResponse::Scalar(Location::new(url, range)));
+                }
                 let query = &self.queries.jinja_objects;
                 let objects = objects_query(query, tree, point, &writter.content, false);
                 let mut res = objects.is_ident(point).and_then(|ident| {
@@ -504,6 +710,15 @@ impl LspFiles {
         &self,
         params: tower_lsp::lsp_types::ReferenceParams,
     ) -> Option<Vec<Location>> {
+        let uri = params.text_document_position.text_document.uri.as_str();
+        let position = params.text_document_position.position;
+        let point = Point::new(position.line as usize, position.character as usize);
+        if let Some(overrides) = self.block_overrides(uri, point) {
+            return Some(overrides);
+        }
+        if let Some((macro_uri, definition)) = self.macro_at(uri, point) {
+            return self.macro_references(&macro_uri, &definition);
+        }
         let definition = self.goto_definition(GotoDefinitionParams {
             text_document_position_params: params.text_document_position,
             work_done_progress_params: WorkDoneProgressParams {
@@ -605,6 +820,28 @@ impl LspFiles {
                 if let Some(code_action) = code_action {
                     return Some(JinjaCodeAction::CreateTemplate(code_action.name.to_owned()));
                 }
+                if let Some(fix) = self.scope_fix(&uri, point) {
+                    return Some(fix);
+                }
+                let fix = self
+                    .filter_errors(&uri)
+                    .into_iter()
+                    .chain(self.argument_errors(&uri))
+                    .find_map(|(diagnostic, identifier)| match diagnostic {
+                        JinjaDiagnostic::UnknownFilter(Some(name))
+                        | JinjaDiagnostic::UnknownTest(Some(name))
+                        | JinjaDiagnostic::UnknownArgument(Some(name))
+                            if point >= identifier.start && point <= identifier.end =>
+                        {
+                            Some((name, identifier))
+                        }
+                        _ => None,
+                    });
+                if let Some((name, identifier)) = fix {
+                    let range = to_range((identifier.start, identifier.end));
+                    let title = format!("Change to `{name}`");
+                    return Some(JinjaCodeAction::QuickFix(title, TextEdit::new(range, name)));
+                }
                 let _objects = objects_query(query, tree, point, &writter.content, false);
                 None
                 // if objects.in_expr(point) {
@@ -619,8 +856,87 @@ impl LspFiles {
                 if let Some(code_action) = code_action {
                     return Some(JinjaCodeAction::CreateTemplate(code_action.name.to_owned()));
                 }
-                None
+                let unused = self
+                    .unused_context(&uri)
+                    .into_iter()
+                    .find(|key| point >= key.start && point <= key.end);
+                if let Some(key) = unused {
+                    let range = to_range(context_removal(tree, &key)?);
+                    let title = format!("Remove `{}` from context", key.name);
+                    return Some(JinjaCodeAction::QuickFix(
+                        title,
+                        TextEdit::new(range, String::new()),
+                    ));
+                }
+                let (call, names) = self
+                    .missing_context(&uri)
+                    .into_iter()
+                    .find(|(call, _)| point >= call.template.start && point <= call.template.end)?;
+                let content = self.document_content(&uri)?;
+                let (range, text) = context_insertion(tree, &content, &call, &names)?;
+                let edit = TextEdit::new(to_range(range), text);
+                let names: Vec<String> = names.iter().map(|name| format!("`{name}`")).collect();
+                let title = format!("Pass {} to template", names.join(", "));
+                Some(JinjaCodeAction::QuickFix(title, edit))
+            }
+        }
+    }
+
+    /// Inserts end keyword of unclosed scope under `point` (and of unclosed
+    /// scopes inside of it) at the end of file, or removes stray end statement.
+    fn scope_fix(&self, uri: &str, point: Point) -> Option<JinjaCodeAction> {
+        let errors = self.scope_errors.get(uri)?;
+        let error = errors.iter().find(|error| match error {
+            ScopeError::Unclosed(scope) | ScopeError::UnexpectedEnd(scope) => {
+                point >= scope.start && point <= scope.end
             }
+            _ => false,
+        })?;
+        match error {
+            ScopeError::Unclosed(scope) => {
+                let mut unclosed: Vec<&Scope> = errors
+                    .iter()
+                    .filter_map(|error| match error {
+                        ScopeError::Unclosed(inner) if inner.start >= scope.start => Some(inner),
+                        _ => None,
+                    })
+                    .collect();
+                unclosed.sort_by_key(|inner| Reverse(inner.start));
+                let tags: Vec<String> = unclosed
+                    .iter()
+                    .map(|inner| format!("{{% {} %}}", inner.end_keyword()))
+                    .collect();
+                let document = self.documents.get(uri)?;
+                let last = document.len_lines() - 1;
+                let line = document.line(last);
+                let position = Position::new(last as u32, line.len_utf16_cu() as u32);
+                let text = tags.join("\n");
+                let text = if line.len_chars() == 0 {
+                    format!("{text}\n")
+                } else {
+                    format!("\n{text}")
+                };
+                let title = format!("Insert `{}`", tags.concat());
+                let edit = TextEdit::new(Range::new(position, position), text);
+                Some(JinjaCodeAction::QuickFix(title, edit))
+            }
+            ScopeError::UnexpectedEnd(scope) => {
+                let tree = self.trees.get(&LangType::Template)?.get(uri)?;
+                let content = self.document_content(uri)?;
+                let statement = tree
+                    .root_node()
+                    .named_descendant_for_point_range(scope.start, scope.end)?
+                    .parent()
+                    .filter(|node| node.kind() == "statement")?;
+                let text = statement.utf8_text(content.as_bytes()).ok()?;
+                let title = format!("Remove `{text}`");
+                let range = to_range((statement.start_position(), statement.end_position()));
+                Some(JinjaCodeAction::QuickFix(
+                    title,
+                    TextEdit::new(range, String::new()),
+                ))
+            }
+            _ => None,
         }
     }
 
@@ -685,6 +1001,16 @@ impl LspFiles {
                 });
                 Some(commands)
             }
+            JinjaCodeAction::QuickFix(title, edit) => {
+                let changes = HashMap::from([(param.text_document.uri, vec![edit])]);
+                commands.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title,
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    edit: Some(WorkspaceEdit::new(changes)),
+                    ..Default::default()
+                }));
+                Some(commands)
+            }
         }
     }
 
@@ -699,10 +1025,12 @@ impl LspFiles {
     }
 
     pub fn read_trees(&self, diags: &mut HashMap<String, Vec<(JinjaDiagnostic, Identifier)>>) {
-        for tree in self.trees.get(&LangType::Template).unwrap() {
-            let errors = self.read_tree(tree.0);
-            if let Some(errors) = errors {
-                diags.insert(String::from(tree.0), errors);
+        for lang_type in [LangType::Template, LangType::Backend] {
+            for tree in self.trees.get(&lang_type).unwrap() {
+                let errors = self.read_tree(tree.0);
+                if let Some(errors) = errors {
+                    diags.insert(String::from(tree.0), errors);
+                }
             }
         }
     }
@@ -986,7 +1314,14 @@ impl LspFiles {
         params: tower_lsp::lsp_types::DocumentSymbolParams,
     ) -> Option<DocumentSymbolResponse> {
         let mut symbols = vec![];
-        let variables = self.variables.get(params.text_document.uri.as_str())?;
+        let uri = params.text_document.uri.as_str();
+        let variables = self.variables.get(uri)?;
+        if self.config.file_ext(&Path::new(uri)) == Some(LangType::Template) {
+            let tree = self.trees.get(&LangType::Template)?.get(uri)?;
+            let content = self.document_content(uri)?;
+            let symbols = nested_symbols(tree, &content, &self.queries, variables)?;
+            return Some(DocumentSymbolResponse::Nested(symbols));
+        }
         for variable in variables {
             #[allow(deprecated)]
             let symbol = DocumentSymbol {
@@ -1004,131 +1339,1815 @@ impl LspFiles {
         Some(DocumentSymbolResponse::Nested(symbols))
     }
 
-    pub fn delete_documents(&mut self) {
-        self.documents.clear();
-    }
-
-    pub fn delete_documents_with_id(&mut self, id: String) {
-        let mut ids = vec![];
-        for i in &self.documents {
-            if i.0.contains(&id) {
-                ids.push(i.0.clone());
+    pub fn semantic_tokens(&self, uri: &Url, range: Option<Range>) -> Option<SemanticTokens> {
+        if self.config.file_ext(&Path::new(uri.as_str()))? != LangType::Template {
+            return None;
+        }
+        let variables = self.variables.get(uri.as_str())?;
+        let mut tokens = vec![];
+        for variable in variables {
+            if variable.identifier_type == IdentifierType::JinjaTemplate {
+                continue;
             }
+            let location = (variable.start, variable.end);
+            tokens.push(JinjaToken::identifier(
+                location,
+                &variable.identifier_type,
+                true,
+            ));
         }
-        for i in ids {
-            self.documents.remove(&i);
-            self.variables.remove(&i);
-            if let Some(templates) = self.trees.get_mut(&LangType::Template) {
-                templates.remove(&i);
+        for block in self.block_names(uri.as_str()) {
+            let location = (block.start, block.end);
+            tokens.push(JinjaToken::identifier(
+                location,
+                &block.identifier_type,
+                false,
+            ));
+        }
+        let mut macros = HashSet::new();
+        let mut modules = HashSet::new();
+        for import in self.template_imports(uri.as_str()).unwrap_or_default() {
+            match &import {
+                Import::From { .. } => {
+                    for (name, alias) in import.imported_names() {
+                        let location = (name.start, name.end);
+                        tokens.push(JinjaToken::new(location, SemanticTokenType::MACRO));
+                        let name = alias.unwrap_or(name);
+                        macros.insert(name.name.to_owned());
+                        if let Some(alias) = alias {
+                            tokens.push(
+                                JinjaToken::new((alias.start, alias.end), SemanticTokenType::MACRO)
+                                    .with_modifier(SemanticTokenModifier::DECLARATION),
+                            );
+                        }
+                    }
+                }
+                Import::Import { identifier, .. } => {
+                    modules.insert(identifier.name.to_owned());
+                    let location = (identifier.start, identifier.end);
+                    tokens.push(
+                        JinjaToken::new(location, SemanticTokenType::NAMESPACE)
+                            .with_modifier(SemanticTokenModifier::DECLARATION),
+                    );
+                }
+                _ => {}
+            }
+        }
+        let backend_variables: HashSet<&str> = self
+            .variables
+            .iter()
+            .filter(|(file, _)| {
+                self.config.file_ext(&Path::new(file.as_str())) == Some(LangType::Backend)
+            })
+            .flat_map(|(_, variables)| variables)
+            .filter(|variable| variable.identifier_type == IdentifierType::BackendVariable)
+            .map(|variable| variable.name.split('.').next().unwrap_or(&variable.name))
+            .collect();
+        for object in self.read_objects(uri.to_owned())? {
+            let mut fields = object.fields.iter();
+            let Some((name, location)) = fields.next() else {
+                continue;
+            };
+            let function = JinjaToken::new(*location, SemanticTokenType::FUNCTION)
+                .with_modifier(SemanticTokenModifier::DEFAULT_LIBRARY);
+            if object.is_test || object.is_filter {
+                tokens.push(function);
+                continue;
+            }
+            let mut field_type = SemanticTokenType::PROPERTY;
+            if let Some(definition) = resolve_definition(variables, name, location.0) {
+                tokens.push(JinjaToken::identifier(
+                    *location,
+                    &definition.identifier_type,
+                    false,
+                ));
+            } else if modules.contains(name) {
+                tokens.push(JinjaToken::new(*location, SemanticTokenType::NAMESPACE));
+                field_type = SemanticTokenType::MACRO;
+            } else if macros.contains(name) {
+                tokens.push(JinjaToken::new(*location, SemanticTokenType::MACRO));
+            } else if backend_variables.contains(name.as_str()) {
+                tokens.push(JinjaToken::identifier(
+                    *location,
+                    &IdentifierType::BackendVariable,
+                    false,
+                ));
+            } else {
+                tokens.push(JinjaToken::identifier(
+                    *location,
+                    &IdentifierType::UndefinedVariable,
+                    false,
+                ));
+            }
+            if let Some((_, location)) = fields.next() {
+                tokens.push(JinjaToken::new(*location, field_type));
+            }
+            for (_, location) in fields {
+                tokens.push(JinjaToken::new(*location, SemanticTokenType::PROPERTY));
             }
         }
+        if let Some(range) = range {
+            let start = to_point(range.start);
+            let end = to_point(range.end);
+            tokens.retain(|token| token.end > start && token.start < end);
+        }
+        Some(SemanticTokens {
+            result_id: None,
+            data: encode_tokens(tokens),
+        })
     }
-}
 
-impl Default for LspFiles {
-    fn default() -> Self {
-        let mut trees = HashMap::new();
-        trees.insert(LangType::Template, HashMap::new());
-        trees.insert(LangType::Backend, HashMap::new());
-        let diagnostics_task = None;
-        let main_channel = None;
-        Self {
-            trees,
-            parsers: Parsers::default(),
-            queries: Queries::default(),
-            documents: HashMap::new(),
-            config: JinjaConfig::default(),
-            diagnostics_task,
-            main_channel,
-            variables: HashMap::default(),
-            is_vscode: false,
-            code_actions: HashMap::default(),
-            ignore_globals: false,
-            scope_errors: HashMap::default(),
+    pub fn signature_help(&self, params: SignatureHelpParams) -> Option<SignatureHelp> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        if self.config.file_ext(&Path::new(uri.as_str()))? != LangType::Template {
+            return None;
         }
-    }
-}
+        let rope = self.documents.get(uri.as_str())?;
+        let offset = position_byte(rope, position)?;
+        let content = self.document_content(uri.as_str())?;
+        let tree = self.trees.get(&LangType::Template)?.get(uri.as_str())?;
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let node = root
+            .children(&mut cursor)
+            .find(|node| node.start_byte() < offset && offset <= node.end_byte())?;
+        let context = call_context(node, &content, offset)?;
+        let (definition, parameters) = self.macro_signature(uri.as_str(), &context.callee)?;
 
-impl Clone for LspFiles {
-    fn clone(&self) -> Self {
-        let trees = self.trees.clone();
-        let parsers = Parsers::default();
-        let queries = Queries::default();
-        let documents = self.documents.clone();
-        let main_channel = self.main_channel.clone();
-        let variables = self.variables.clone();
-        let is_vscode = self.is_vscode;
-        let code_actions = self.code_actions.clone();
-        let config = self.config.clone();
-        let scope_errors = self.scope_errors.clone();
-        let task = None;
-        Self {
-            trees,
-            documents,
-            parsers,
-            queries,
-            config,
-            main_channel,
-            variables,
-            code_actions,
-            is_vscode,
-            diagnostics_task: task,
-            ignore_globals: self.ignore_globals,
-            scope_errors,
+        let mut label = format!("{}(", definition.name);
+        let mut parameter_labels = vec![];
+        for (index, (name, default)) in parameters.iter().enumerate() {
+            if index > 0 {
+                label.push_str(", ");
+            }
+            let start = label.encode_utf16().count() as u32;
+            label.push_str(name);
+            if let Some(default) = default {
+                label.push('=');
+                label.push_str(default);
+            }
+            let end = label.encode_utf16().count() as u32;
+            parameter_labels.push(ParameterInformation {
+                label: ParameterLabel::LabelOffsets([start, end]),
+                documentation: None,
+            });
+        }
+        label.push(')');
+        let active_parameter = match &context.keyword {
+            Some(keyword) => parameters.iter().position(|(name, _)| name == keyword),
+            None => (context.argument < parameters.len()).then_some(context.argument),
         }
+        .map(|index| index as u32);
+        Some(SignatureHelp {
+            signatures: vec![SignatureInformation {
+                label,
+                documentation: None,
+                parameters: Some(parameter_labels),
+                active_parameter,
+            }],
+            active_signature: Some(0),
+            active_parameter,
+        })
     }
-}
 
-#[derive(Default, Debug)]
-pub struct FileContent {
-    pub content: String,
-}
+    /// Definition of called macro with its parameters.
+    fn macro_signature(&self, uri: &str, callee: &str) -> Option<(Identifier, Vec<Parameter>)> {
+        let (macro_uri, definition) = self.macro_definition(uri, callee)?;
+        let parameters = self.macro_parameters_of(&macro_uri, &definition)?;
+        Some((definition, parameters))
+    }
 
-impl std::io::Write for FileContent {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        if let Ok(b) = std::str::from_utf8(buf) {
-            self.content.push_str(b);
-        }
-        Ok(buf.len())
+    /// Parameters of macro from its `MacroParameter` identifiers.
+    fn macro_parameters_of(&self, uri: &str, definition: &Identifier) -> Option<Vec<Parameter>> {
+        let tree = self.trees.get(&LangType::Template)?.get(uri)?;
+        let content = self.document_content(uri)?;
+        let root = tree.root_node();
+        let statement = root.named_descendant_for_point_range(definition.start, definition.end)?;
+        let statement = statement
+            .parent()
+            .filter(|node| node.kind() == "statement")?;
+        let mut names: Vec<Identifier> = self
+            .variables
+            .get(uri)?
+            .iter()
+            .filter(|variable| {
+                variable.identifier_type == IdentifierType::MacroParameter
+                    && variable.start >= statement.start_position()
+                    && variable.end <= statement.end_position()
+            })
+            .cloned()
+            .collect();
+        names.sort_by_key(|name| name.start);
+        Some(macro_parameters(statement, &content, &names))
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        Ok(())
+    /// Names of macro parameters, without default values.
+    fn macro_parameter_names(&self, uri: &str, definition: &Identifier) -> Option<Vec<String>> {
+        let parameters = self.macro_parameters_of(uri, definition)?;
+        Some(parameters.into_iter().map(|(name, _)| name).collect())
     }
-}
 
-#[derive(Default, Debug)]
-pub struct FileWriter {
-    pub content: String,
-}
+    /// Whether body of macro uses `varargs` and `kwargs`.
+    fn macro_extra_arguments(&self, uri: &str, definition: &Identifier) -> (bool, bool) {
+        let body = self
+            .macro_bodies(uri)
+            .into_iter()
+            .find(|(name, _)| name.start == definition.start);
+        let (Some((_, (start, end))), Some(tree), Some(content)) = (
+            body,
+            self.trees
+                .get(&LangType::Template)
+                .and_then(|trees| trees.get(uri)),
+            self.document_content(uri),
+        ) else {
+            return (false, false);
+        };
+        let (mut varargs, mut kwargs) = (false, false);
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        for node in root.named_children(&mut cursor) {
+            if node.end_position() < start || node.start_position() > end {
+                continue;
+            }
+            let mut identifiers = node.walk();
+            for child in node.named_children(&mut identifiers) {
+                match child.utf8_text(content.as_bytes()) {
+                    Ok("varargs") => varargs = true,
+                    Ok("kwargs") => kwargs = true,
+                    _ => {}
+                }
+            }
+        }
+        (varargs, kwargs)
+    }
 
-impl std::io::Write for FileWriter {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        if let Ok(b) = std::str::from_utf8(buf) {
-            self.content.push_str(b);
+    /// Macro calls in statements and expressions of template, offsets are
+    /// relative to start of file. Macro definitions are skipped.
+    fn template_calls(&self, uri: &str) -> Vec<MacroCall> {
+        let mut calls = vec![];
+        let Some(tree) = self
+            .trees
+            .get(&LangType::Template)
+            .and_then(|trees| trees.get(uri))
+        else {
+            return calls;
+        };
+        let Some(content) = self.document_content(uri) else {
+            return calls;
+        };
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        for node in root.named_children(&mut cursor) {
+            if !matches!(node.kind(), "statement" | "expression") {
+                continue;
+            }
+            let mut keywords = node.walk();
+            let definition = node
+                .named_children(&mut keywords)
+                .find(|child| child.kind() == "keyword")
+                .is_some_and(|keyword| keyword.utf8_text(content.as_bytes()) == Ok("macro"));
+            if definition {
+                continue;
+            }
+            calls.extend(macro_calls(node, &content));
         }
-        Ok(buf.len())
+        calls
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        Ok(())
+    /// Macros defined in template with range from `{% macro %}` to `{% endmacro %}`.
+    fn macro_bodies(&self, uri: &str) -> Vec<(Identifier, (Point, Point))> {
+        let mut bodies = vec![];
+        let (Some(tree), Some(content), Some(variables)) = (
+            self.trees
+                .get(&LangType::Template)
+                .and_then(|trees| trees.get(uri)),
+            self.document_content(uri),
+            self.variables.get(uri),
+        ) else {
+            return bodies;
+        };
+        let query = &self.queries.jinja_definitions;
+        let definitions = definition_query(query, tree, Point::default(), &content, true);
+        let statements: Vec<(Point, Point)> =
+            statement_keywords(tree.root_node()).into_keys().collect();
+        let statement_at = |point: Point| {
+            statements
+                .iter()
+                .find(|statement| statement.0 <= point && point <= statement.1)
+                .copied()
+        };
+        for scope in &definitions.scopes {
+            if scope.keyword != "macro" {
+                continue;
+            }
+            let (Some(opener), Some(closer)) = (statement_at(scope.start), statement_at(scope.end))
+            else {
+                continue;
+            };
+            let name = variables.iter().find(|variable| {
+                variable.identifier_type == IdentifierType::MacroName
+                    && opener.0 <= variable.start
+                    && variable.end <= opener.1
+            });
+            if let Some(name) = name {
+                bodies.push((name.to_owned(), (opener.0, closer.1)));
+            }
+        }
+        bodies.sort_by_key(|(name, _)| name.start);
+        bodies
     }
-}
 
-fn dedup_locations(references: &mut Vec<Location>) {
-    references.sort_by(|a, b| {
-        a.uri
-            .as_str()
-            .cmp(b.uri.as_str())
-            .then(a.range.start.line.cmp(&b.range.start.line))
-            .then(a.range.start.character.cmp(&b.range.start.character))
-            .then(a.range.end.line.cmp(&b.range.end.line))
-            .then(a.range.end.character.cmp(&b.range.end.character))
-    });
-    references.dedup();
-}
+    fn call_hierarchy_item(&self, uri: &str, name: &Identifier) -> Option<CallHierarchyItem> {
+        let url = Url::parse(uri).ok()?;
+        let range = self
+            .macro_bodies(uri)
+            .into_iter()
+            .find(|(body, _)| body.start == name.start)
+            .map_or((name.start, name.end), |(_, range)| range);
+        Some(CallHierarchyItem {
+            name: name.name.to_owned(),
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            detail: Path::new(url.path())
+                .file_name()
+                .and_then(|file| file.to_str())
+                .map(String::from),
+            uri: url,
+            range: to_range(range),
+            selection_range: to_range((name.start, name.end)),
+            data: None,
+        })
+    }
 
-pub enum JinjaCodeAction {
-    Reset,
+    /// Macro definition under the cursor or macro that is called under the cursor.
+    pub fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Option<Vec<CallHierarchyItem>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .as_str();
+        let position = params.text_document_position_params.position;
+        let point = Point::new(position.line as usize, position.character as usize);
+        if self.config.file_ext(&Path::new(uri))? != LangType::Template {
+            return None;
+        }
+        let definition = self.variables.get(uri)?.iter().find(|variable| {
+            variable.identifier_type == IdentifierType::MacroName
+                && variable.start <= point
+                && point <= variable.end
+        });
+        let (macro_uri, definition) = match definition {
+            Some(definition) => (uri.to_owned(), definition.to_owned()),
+            None => {
+                let rope = self.documents.get(uri)?;
+                let call = self.template_calls(uri).into_iter().find(|call| {
+                    let start = byte_point(rope, call.start);
+                    let end = byte_point(rope, call.start + call.callee.len());
+                    start.is_some_and(|start| start <= point) && end.is_some_and(|end| point <= end)
+                })?;
+                self.macro_definition(uri, &call.callee)?
+            }
+        };
+        Some(vec![self.call_hierarchy_item(&macro_uri, &definition)?])
+    }
+
+    /// Calls of macros with their resolved definition, `(uri, name)`.
+    fn resolved_calls(&self, uri: &str) -> Vec<(Range, String, Identifier)> {
+        let Some(rope) = self.documents.get(uri) else {
+            return vec![];
+        };
+        self.template_calls(uri)
+            .into_iter()
+            .filter_map(|call| {
+                let start = byte_point(rope, call.start)?;
+                let end = byte_point(rope, call.start + call.callee.len())?;
+                let (macro_uri, definition) = self.macro_definition(uri, &call.callee)?;
+                Some((to_range((start, end)), macro_uri, definition))
+            })
+            .collect()
+    }
+
+    /// Macros and templates that call macro from `item`.
+    pub fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Option<Vec<CallHierarchyIncomingCall>> {
+        let target_uri = params.item.uri.as_str();
+        let target = to_point(params.item.selection_range.start);
+        let templates = self.trees.get(&LangType::Template)?;
+        let mut uris: Vec<&String> = templates.keys().collect();
+        uris.sort();
+        let mut incoming = vec![];
+        for uri in uris {
+            let bodies = self.macro_bodies(uri);
+            let mut callers: Vec<(Option<&Identifier>, Vec<Range>)> = vec![];
+            for (range, macro_uri, definition) in self.resolved_calls(uri) {
+                if macro_uri != target_uri || definition.start != target {
+                    continue;
+                }
+                let point = to_point(range.start);
+                let caller = bodies
+                    .iter()
+                    .find(|(_, body)| body.0 <= point && point <= body.1)
+                    .map(|(name, _)| name);
+                match callers.iter_mut().find(|(name, _)| *name == caller) {
+                    Some((_, ranges)) => ranges.push(range),
+                    None => callers.push((caller, vec![range])),
+                }
+            }
+            for (caller, from_ranges) in callers {
+                let from = match caller {
+                    Some(name) => self.call_hierarchy_item(uri, name),
+                    None => file_call_hierarchy_item(uri),
+                };
+                let Some(from) = from else {
+                    continue;
+                };
+                incoming.push(CallHierarchyIncomingCall { from, from_ranges });
+            }
+        }
+        Some(incoming)
+    }
+
+    /// Macros that are called in body of macro from `item`.
+    pub fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Option<Vec<CallHierarchyOutgoingCall>> {
+        let uri = params.item.uri.as_str();
+        let name = to_point(params.item.selection_range.start);
+        let (_, body) = self
+            .macro_bodies(uri)
+            .into_iter()
+            .find(|(definition, _)| definition.start == name)?;
+        let calls = self
+            .resolved_calls(uri)
+            .into_iter()
+            .filter(|(range, _, _)| {
+                let point = to_point(range.start);
+                body.0 <= point && point <= body.1
+            });
+        let outgoing = calls_by_callee(calls, |(_, macro_uri, definition)| {
+            self.call_hierarchy_item(macro_uri, definition)
+        })
+        .into_iter()
+        .map(|(to, calls)| CallHierarchyOutgoingCall {
+            to,
+            from_ranges: calls.into_iter().map(|(range, _, _)| range).collect(),
+        })
+        .collect();
+        Some(outgoing)
+    }
+
+    /// Parameter names before positional macro arguments and source of
+    /// backend variables at their first use.
+    pub fn inlay_hint(&self, params: InlayHintParams) -> Option<Vec<InlayHint>> {
+        let uri = params.text_document.uri.as_str();
+        if self.config.file_ext(&Path::new(uri))? != LangType::Template {
+            return None;
+        }
+        let rope = self.documents.get(uri)?;
+        let range = params.range;
+        let start = Point::new(range.start.line as usize, range.start.character as usize);
+        let end = Point::new(range.end.line as usize, range.end.character as usize);
+        let mut hints = vec![];
+        for call in self.template_calls(uri) {
+            let Some(call_start) = byte_point(rope, call.start) else {
+                continue;
+            };
+            if call_start < start || call_start > end {
+                continue;
+            }
+            let Some((_, parameters)) = self.macro_signature(uri, &call.callee) else {
+                continue;
+            };
+            let positional = call
+                .arguments
+                .iter()
+                .take_while(|argument| argument.keyword.is_none());
+            for (argument, (name, _)) in positional.zip(&parameters) {
+                if &argument.text == name {
+                    continue;
+                }
+                let Some(position) = byte_point(rope, argument.start) else {
+                    continue;
+                };
+                hints.push(InlayHint {
+                    position: to_position2(position),
+                    label: InlayHintLabel::String(format!("{name}:")),
+                    kind: Some(InlayHintKind::PARAMETER),
+                    text_edits: None,
+                    tooltip: None,
+                    padding_left: None,
+                    padding_right: Some(true),
+                    data: None,
+                });
+            }
+        }
+
+        let variables = self.variables.get(uri)?;
+        let mut sources = self.graph.context_sources(uri);
+        for (file, context) in &self.contexts {
+            for global in &context.globals {
+                let name = global.name.split('.').next().unwrap_or(&global.name);
+                let files = sources.entry(name.to_owned()).or_default();
+                if !files.contains(file) {
+                    files.push(file.to_owned());
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        for object in self.read_objects(params.text_document.uri.to_owned())? {
+            let Some((name, location)) = object.fields.first() else {
+                continue;
+            };
+            if object.is_test || object.is_filter {
+                continue;
+            }
+            if resolve_definition(variables, name, location.0).is_some() {
+                continue;
+            }
+            let Some(files) = sources.get(name.as_str()) else {
+                continue;
+            };
+            if !seen.insert(name.to_owned()) || location.1 < start || location.0 > end {
+                continue;
+            }
+            let mut files: Vec<String> = files.iter().map(|file| relative_path(file)).collect();
+            files.sort();
+            hints.push(InlayHint {
+                position: to_position2(location.1),
+                label: InlayHintLabel::String(format!("backend: {}", files.join(", "))),
+                kind: None,
+                text_edits: None,
+                tooltip: None,
+                padding_left: Some(true),
+                padding_right: None,
+                data: None,
+            });
+        }
+        Some(hints)
+    }
+
+    /// Macro that is called as `callee` in template, defined in same template
+    /// or imported with `{% from %}` or `{% import %}`.
+    fn macro_definition(&self, uri: &str, callee: &str) -> Option<(String, Identifier)> {
+        let local = self.variables.get(uri)?.iter().find(|variable| {
+            variable.identifier_type == IdentifierType::MacroName && variable.name == callee
+        });
+        if let Some(local) = local {
+            return Some((uri.to_owned(), local.to_owned()));
+        }
+        let imports = self.template_imports(uri).unwrap_or_default();
+        let (template, name) = imported_macro_name(&imports, callee)?;
+        match self.imported_macro(&template.name, name)? {
+            RenameTarget::Definition { uri, definition } => Some((uri, definition)),
+            _ => None,
+        }
+    }
+
+    /// Macros, blocks and backend variables from all files that match query.
+    pub fn workspace_symbols(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Option<Vec<SymbolInformation>> {
+        let mut symbols = vec![];
+        for (uri, variables) in &self.variables {
+            let Ok(url) = Url::parse(uri) else {
+                continue;
+            };
+            let container_name = Path::new(url.path())
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(String::from);
+            for variable in variables {
+                match variable.identifier_type {
+                    IdentifierType::MacroName
+                    | IdentifierType::TemplateBlock
+                    | IdentifierType::BackendVariable => {}
+                    _ => continue,
+                }
+                let Some(score) = fuzzy_score(&params.query, &variable.name) else {
+                    continue;
+                };
+                #[allow(deprecated)]
+                let symbol = SymbolInformation {
+                    name: variable.name.to_owned(),
+                    kind: variable.identifier_type.symbol_kind(),
+                    tags: None,
+                    deprecated: None,
+                    location: Location::new(
+                        url.to_owned(),
+                        to_range((variable.start, variable.end)),
+                    ),
+                    container_name: container_name.to_owned(),
+                };
+                symbols.push((score, symbol));
+            }
+        }
+        symbols.sort_by(|a, b| {
+            let a_key = (
+                a.0,
+                &a.1.name,
+                a.1.location.uri.as_str(),
+                a.1.location.range.start,
+            );
+            let b_key = (
+                b.0,
+                &b.1.name,
+                b.1.location.uri.as_str(),
+                b.1.location.range.start,
+            );
+            a_key
+                .partial_cmp(&b_key)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        symbols.dedup_by(|a, b| a.1.name == b.1.name && a.1.location == b.1.location);
+        Some(symbols.into_iter().map(|(_, symbol)| symbol).collect())
+    }
+
+    pub fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Option<Vec<DocumentHighlight>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let point = to_point(params.text_document_position_params.position);
+        if self.config.file_ext(&Path::new(uri.as_str()))? != LangType::Template {
+            return None;
+        }
+        if let Some(highlights) = self.statement_highlights(uri.as_str(), point) {
+            return Some(highlights);
+        }
+        let highlight = |location: (Point, Point), kind| DocumentHighlight {
+            range: to_range(location),
+            kind: Some(kind),
+        };
+        let objects = self.read_objects(uri.to_owned())?;
+        let variables = self.variables.get(uri.as_str())?;
+        let mut highlights = vec![];
+        match self.rename_target(uri.as_str(), point) {
+            Some((_, RenameTarget::Definition { uri: other, .. })) if other != uri.as_str() => {
+                let (object, index) = object_at(&objects, point)?;
+                if index == 1 {
+                    let module = &object.fields.first()?.0;
+                    for location in module_occurrences(&objects, module, &object.fields[1].0) {
+                        highlights.push(highlight(location, DocumentHighlightKind::READ));
+                    }
+                    return Some(highlights);
+                }
+                for import in self.template_imports(uri.as_str()).unwrap_or_default() {
+                    for (name, alias) in import.imported_names() {
+                        if alias.is_none() && name.name == object.name {
+                            let location = (name.start, name.end);
+                            highlights.push(highlight(location, DocumentHighlightKind::WRITE));
+                        }
+                    }
+                }
+                for location in unresolved_occurrences(&objects, variables, &object.name) {
+                    if !highlights
+                        .iter()
+                        .any(|item| item.range == to_range(location))
+                    {
+                        highlights.push(highlight(location, DocumentHighlightKind::READ));
+                    }
+                }
+            }
+            Some((_, RenameTarget::Definition { definition, .. })) => {
+                let occurrences = definition_occurrences(&objects, variables, &definition);
+                for (index, location) in occurrences.into_iter().enumerate() {
+                    let kind = match index {
+                        0 => DocumentHighlightKind::WRITE,
+                        _ => DocumentHighlightKind::READ,
+                    };
+                    highlights.push(highlight(location, kind));
+                }
+            }
+            Some((
+                _,
+                RenameTarget::Alias {
+                    alias, location, ..
+                },
+            )) => {
+                highlights.push(highlight(location, DocumentHighlightKind::WRITE));
+                for location in unresolved_occurrences(&objects, variables, &alias) {
+                    highlights.push(highlight(location, DocumentHighlightKind::READ));
+                }
+            }
+            Some((_, RenameTarget::Block { name, .. })) => {
+                for block in self.block_names(uri.as_str()) {
+                    if block.name == name {
+                        let location = (block.start, block.end);
+                        highlights.push(highlight(location, DocumentHighlightKind::TEXT));
+                    }
+                }
+            }
+            Some((_, RenameTarget::Template { .. })) => return None,
+            None => {
+                let (object, index) = object_at(&objects, point)?;
+                if index != 0 || object.is_filter || object.is_test {
+                    return None;
+                }
+                for location in unresolved_occurrences(&objects, variables, &object.name) {
+                    highlights.push(highlight(location, DocumentHighlightKind::READ));
+                }
+            }
+        }
+        Some(highlights)
+    }
+
+    /// Keywords of statements that open, continue and close the same block.
+    fn statement_highlights(&self, uri: &str, point: Point) -> Option<Vec<DocumentHighlight>> {
+        let tree = self.trees.get(&LangType::Template)?.get(uri)?;
+        let content = self.document_content(uri)?;
+        let keywords = statement_keywords(tree.root_node());
+        let statement = keywords
+            .iter()
+            .find(|(_, keyword)| {
+                keyword.start_position() <= point && point <= keyword.end_position()
+            })
+            .map(|(statement, _)| *statement)?;
+        let statements: Vec<(Point, Point)> = keywords.keys().copied().collect();
+        let query = &self.queries.jinja_definitions;
+        let definitions = definition_query(query, tree, Point::default(), &content, true);
+        let matching = matching_statements(&definitions.scopes, &statements, statement);
+        if matching.len() < 2 {
+            return None;
+        }
+        let highlights = matching
+            .iter()
+            .filter_map(|statement| keywords.get(statement))
+            .map(|keyword| DocumentHighlight {
+                range: to_range((keyword.start_position(), keyword.end_position())),
+                kind: Some(DocumentHighlightKind::TEXT),
+            })
+            .collect();
+        Some(highlights)
+    }
+
+    /// Keyword of opening tag with keyword of end tag (without `end`), or
+    /// block name with name repeated in `{% endblock name %}`.
+    pub fn linked_editing_range(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> Option<LinkedEditingRanges> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .as_str();
+        let position = params.text_document_position_params.position;
+        let point = Point::new(position.line as usize, position.character as usize);
+        let tree = self.trees.get(&LangType::Template)?.get(uri)?;
+        let content = self.document_content(uri)?;
+        let query = &self.queries.jinja_definitions;
+        let definitions = definition_query(query, tree, Point::default(), &content, true);
+        let ranges = linked_ranges(tree, &content, &definitions.scopes, point)?;
+        Some(LinkedEditingRanges {
+            ranges: ranges.into_iter().map(to_range).collect(),
+            word_pattern: None,
+        })
+    }
+
+    pub fn folding_ranges(&self, params: FoldingRangeParams) -> Option<Vec<FoldingRange>> {
+        let uri = params.text_document.uri.as_str();
+        let tree = self.trees.get(&LangType::Template)?.get(uri)?;
+        let content = self.document_content(uri)?;
+        Some(folding_ranges(tree, &content, &self.queries))
+    }
+
+    pub fn formatting(&self, params: DocumentFormattingParams) -> Option<Vec<TextEdit>> {
+        self.format_edits(&params.text_document.uri, &params.options, None)
+    }
+
+    pub fn range_formatting(&self, params: DocumentRangeFormattingParams) -> Option<Vec<TextEdit>> {
+        self.format_edits(
+            &params.text_document.uri,
+            &params.options,
+            Some(params.range),
+        )
+    }
+
+    /// Formatter keeps line count, so every changed line becomes one edit.
+    fn format_edits(
+        &self,
+        uri: &Url,
+        options: &FormattingOptions,
+        range: Option<Range>,
+    ) -> Option<Vec<TextEdit>> {
+        if self.config.file_ext(&Path::new(uri.as_str()))? != LangType::Template {
+            return None;
+        }
+        let tree = self.trees.get(&LangType::Template)?.get(uri.as_str())?;
+        let content = self.document_content(uri.as_str())?;
+        let indent_width = self
+            .config
+            .indent_width
+            .unwrap_or(options.tab_size as usize);
+        let indent = if options.insert_spaces || self.config.indent_width.is_some() {
+            " ".repeat(indent_width)
+        } else {
+            String::from("\t")
+        };
+        let format_options = FormatOptions {
+            indent,
+            delimiter_padding: self.config.delimiter_padding.unwrap_or(true),
+            tab_size: options.tab_size as usize,
+        };
+        let formatted = format_template(tree, &content, &format_options)?;
+        let mut edits = vec![];
+        for (row, (old, new)) in content.split('\n').zip(formatted.split('\n')).enumerate() {
+            if old == new {
+                continue;
+            }
+            if let Some(range) = range
+                && (row < range.start.line as usize || row > range.end.line as usize)
+            {
+                continue;
+            }
+            let old = old.strip_suffix('\r').unwrap_or(old);
+            let new = new.strip_suffix('\r').unwrap_or(new);
+            let start = Position::new(row as u32, 0);
+            let end = Position::new(row as u32, old.encode_utf16().count() as u32);
+            edits.push(TextEdit::new(Range::new(start, end), new.to_string()));
+        }
+        Some(edits)
+    }
+
+    pub fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Option<PrepareRenameResponse> {
+        let uri = params.text_document.uri.to_string();
+        let point = to_point(params.position);
+        let (range, target) = self.rename_target(&uri, point)?;
+        let placeholder = match target {
+            RenameTarget::Definition { definition, .. } => definition.name,
+            RenameTarget::Alias { alias, .. } => alias,
+            RenameTarget::Block { name, .. } => name,
+            RenameTarget::Template { name, .. } => name,
+        };
+        Some(PrepareRenameResponse::RangeWithPlaceholder {
+            range: to_range(range),
+            placeholder,
+        })
+    }
+
+    pub fn rename(&self, params: RenameParams) -> Option<WorkspaceEdit> {
+        let uri = params.text_document_position.text_document.uri.to_string();
+        let point = to_point(params.text_document_position.position);
+        let new_name = params.new_name;
+        let (_, target) = self.rename_target(&uri, point)?;
+        if let RenameTarget::Template { path, .. } = target {
+            return self.move_template(path, &new_name);
+        } else if !is_valid_identifier(&new_name) {
+            return None;
+        }
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        let mut add_edits = |uri: &str, locations: Vec<(Point, Point)>| {
+            let Ok(url) = Url::parse(uri) else { return };
+            let edits = changes.entry(url).or_default();
+            for location in locations {
+                let edit = TextEdit::new(to_range(location), new_name.to_owned());
+                if !edits.contains(&edit) {
+                    edits.push(edit);
+                }
+            }
+        };
+        match target {
+            RenameTarget::Definition { uri, definition } => {
+                let objects = self.read_objects(Url::parse(&uri).ok()?)?;
+                let variables = self.variables.get(&uri)?;
+                add_edits(
+                    &uri,
+                    definition_occurrences(&objects, variables, &definition),
+                );
+                if definition.identifier_type == IdentifierType::MacroName {
+                    for (template, locations) in
+                        self.macro_import_occurrences(&uri, &definition, false)
+                    {
+                        add_edits(&template, locations);
+                    }
+                }
+            }
+            RenameTarget::Alias {
+                uri,
+                alias,
+                location,
+            } => {
+                let objects = self.read_objects(Url::parse(&uri).ok()?)?;
+                let variables = self.variables.get(&uri)?;
+                let mut locations = vec![location];
+                locations.extend(unresolved_occurrences(&objects, variables, &alias));
+                add_edits(&uri, locations);
+            }
+            RenameTarget::Block { uri, name } => {
+                for template in self.graph.extends_family(&uri) {
+                    let locations = self
+                        .block_names(&template)
+                        .into_iter()
+                        .filter(|block| block.name == name)
+                        .map(|block| (block.start, block.end))
+                        .collect();
+                    add_edits(&template, locations);
+                }
+            }
+            RenameTarget::Template { .. } => {}
+        }
+        changes.retain(|_, edits| !edits.is_empty());
+        Some(WorkspaceEdit::new(changes))
+    }
+
+    /// Symbol under the cursor that can be renamed, together with its range.
+    fn rename_target(&self, uri: &str, point: Point) -> Option<((Point, Point), RenameTarget)> {
+        let lang_type = self.config.file_ext(&Path::new(uri))?;
+        if let Some(template) = self.template_at(uri, point) {
+            let path = self.template_path(&template.name)?;
+            let target = RenameTarget::Template {
+                name: template.name,
+                path,
+            };
+            return Some(((template.start, template.end), target));
+        } else if lang_type != LangType::Template {
+            return None;
+        }
+        if let Some(block) = self
+            .block_names(uri)
+            .into_iter()
+            .find(|block| block.start <= point && point <= block.end)
+        {
+            let target = RenameTarget::Block {
+                uri: uri.to_owned(),
+                name: block.name,
+            };
+            return Some(((block.start, block.end), target));
+        }
+        let objects = self.read_objects(Url::parse(uri).ok()?)?;
+        let variables = self.variables.get(uri)?;
+        let (object, index) = object_at(&objects, point)?;
+        if object.is_filter || object.is_test {
+            return None;
+        }
+        let location = object.fields.get(index)?.1;
+        let imports = self.template_imports(uri).unwrap_or_default();
+        if index == 1 {
+            let module = &object.fields.first()?.0;
+            let name = &object.fields.get(1)?.0;
+            let template = imports.iter().find_map(|import| match import {
+                Import::Import {
+                    template,
+                    identifier,
+                } if &identifier.name == module => Some(template),
+                _ => None,
+            })?;
+            let target = self.imported_macro(&template.name, name)?;
+            return Some((location, target));
+        } else if index != 0 {
+            return None;
+        }
+        if let Some(definition) = resolve_definition(variables, &object.name, location.0) {
+            let target = RenameTarget::Definition {
+                uri: uri.to_owned(),
+                definition: definition.to_owned(),
+            };
+            return Some((location, target));
+        }
+        for import in &imports {
+            let Import::From { template, .. } = import else {
+                continue;
+            };
+            for (name, alias) in import.imported_names() {
+                if let Some(alias) = alias.filter(|alias| alias.name == object.name) {
+                    let target = RenameTarget::Alias {
+                        uri: uri.to_owned(),
+                        alias: alias.name.to_owned(),
+                        location: (alias.start, alias.end),
+                    };
+                    return Some((location, target));
+                } else if alias.is_none() && name.name == object.name {
+                    let target = self.imported_macro(&template.name, &name.name)?;
+                    return Some((location, target));
+                }
+            }
+        }
+        None
+    }
+
+    /// Places in other templates that use macro `definition` from template
+    /// `uri` through `{% from %}` and `{% import %}`.
+    fn macro_import_occurrences(
+        &self,
+        uri: &str,
+        definition: &Identifier,
+        aliases: bool,
+    ) -> Vec<(String, Vec<(Point, Point)>)> {
+        let mut all = vec![];
+        for (template, import) in self.imports_of(uri) {
+            let Some(objects) = Url::parse(&template)
+                .ok()
+                .and_then(|url| self.read_objects(url))
+            else {
+                continue;
+            };
+            let variables = self.variables.get(&template).cloned().unwrap_or_default();
+            let locations =
+                import_occurrences(&objects, &variables, &import, &definition.name, aliases);
+            all.push((template, locations));
+        }
+        all
+    }
+
+    /// Macro under the cursor: its definition, a call (also through alias or
+    /// module) or a name in `{% from %}` statement.
+    fn macro_at(&self, uri: &str, point: Point) -> Option<(String, Identifier)> {
+        if self.config.file_ext(&Path::new(uri))? != LangType::Template {
+            return None;
+        }
+        let variables = self.variables.get(uri)?;
+        let definition = variables.iter().find(|variable| {
+            variable.identifier_type == IdentifierType::MacroName
+                && variable.start <= point
+                && point <= variable.end
+        });
+        if let Some(definition) = definition {
+            return Some((uri.to_owned(), definition.to_owned()));
+        }
+        let imports = self.template_imports(uri).unwrap_or_default();
+        if let Some((template, name)) = imported_name_at(&imports, point) {
+            return match self.imported_macro(&template.name, &name.name)? {
+                RenameTarget::Definition { uri, definition } => Some((uri, definition)),
+                _ => None,
+            };
+        }
+        let objects = self.read_objects(Url::parse(uri).ok()?)?;
+        let callee = macro_callee(&objects, variables, point)?;
+        self.macro_definition(uri, &callee)
+    }
+
+    /// Definition of macro and all of its uses, in its template and in
+    /// templates that import it.
+    fn macro_references(&self, uri: &str, definition: &Identifier) -> Option<Vec<Location>> {
+        let objects = self.read_objects(Url::parse(uri).ok()?)?;
+        let variables = self.variables.get(uri)?;
+        let mut all = vec![(
+            uri.to_owned(),
+            definition_occurrences(&objects, variables, definition),
+        )];
+        all.extend(self.macro_import_occurrences(uri, definition, true));
+        let mut references = vec![];
+        for (template, locations) in all {
+            let Ok(url) = Url::parse(&template) else {
+                continue;
+            };
+            for location in locations {
+                references.push(Location::new(url.to_owned(), to_range(location)));
+            }
+        }
+        dedup_locations(&mut references);
+        Some(references)
+    }
+
+    /// Signature of macro under the cursor with template where it is defined.
+    pub fn macro_hover(&self, params: &HoverParams) -> Option<String> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .as_str();
+        let point = to_point(params.text_document_position_params.position);
+        let (macro_uri, definition) = self.macro_at(uri, point)?;
+        let parameters = self.macro_parameters_of(&macro_uri, &definition)?;
+        let parameters: Vec<String> = parameters
+            .into_iter()
+            .map(|(name, default)| match default {
+                Some(default) => format!("{name}={default}"),
+                None => name,
+            })
+            .collect();
+        let mut value = format!(
+            "```jinja\n{{% macro {}({}) %}}\n```",
+            definition.name,
+            parameters.join(", ")
+        );
+        if macro_uri != uri {
+            let path = Url::parse(&macro_uri).ok()?.to_file_path().ok()?;
+            let root = std::fs::canonicalize(&self.config.templates).ok()?;
+            if let Some(name) = template_name(&root, &path) {
+                value.push_str(&format!("\n\nImported from `{name}`"));
+            }
+        }
+        Some(value)
+    }
+
+    /// Macro `name` defined at the top of template `template`.
+    fn imported_macro(&self, template: &str, name: &str) -> Option<RenameTarget> {
+        let uri = self.template_uri(template)?;
+        let definition = self.variables.get(&uri)?.iter().find(|variable| {
+            variable.identifier_type == IdentifierType::MacroName && variable.name == name
+        })?;
+        Some(RenameTarget::Definition {
+            definition: definition.to_owned(),
+            uri,
+        })
+    }
+
+    /// Uri of template file, `name` is relative to templates directory.
+    pub fn template_uri(&self, name: &str) -> Option<String> {
+        match self.graph.uri(name) {
+            Some(uri) => Some(uri.to_owned()),
+            None => template_file_uri(&self.config.templates, name),
+        }
+    }
+
+    fn template_path(&self, name: &str) -> Option<PathBuf> {
+        template_file_path(&self.config.templates, name)
+    }
+
+    /// Template path string under the cursor, in templates and in backend files.
+    pub fn template_at(&self, uri: &str, point: Point) -> Option<Identifier> {
+        let lang_type = self.config.file_ext(&Path::new(uri))?;
+        match lang_type {
+            LangType::Template => self
+                .template_imports(uri)?
+                .iter()
+                .find_map(|import| import.get_identifier(point))
+                .cloned(),
+            LangType::Backend => self
+                .variables
+                .get(uri)?
+                .iter()
+                .filter(|variable| variable.identifier_type == IdentifierType::JinjaTemplate)
+                .find(|template| template.start <= point && point <= template.end)
+                .cloned(),
+        }
+    }
+
+    /// Template path strings in template or backend file, with quotes.
+    fn template_references(&self, uri: &str) -> Vec<Identifier> {
+        match self.config.file_ext(&Path::new(uri)) {
+            Some(LangType::Template) => self
+                .template_imports(uri)
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|import| import.templates())
+                .cloned()
+                .collect(),
+            Some(LangType::Backend) => self
+                .variables
+                .get(uri)
+                .into_iter()
+                .flatten()
+                .filter(|variable| variable.identifier_type == IdentifierType::JinjaTemplate)
+                .cloned()
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Links to existing templates, missing ones are reported as diagnostics.
+    pub fn document_link(&self, params: DocumentLinkParams) -> Option<Vec<DocumentLink>> {
+        let uri = params.text_document.uri.as_str();
+        let links = document_links(self.template_references(uri), |name| {
+            self.template_uri(name)
+        });
+        Some(links)
+    }
+
+    /// Template path strings in all files that point to one of the renamed
+    /// templates (or to a template inside of renamed directory).
+    fn template_reference_edits(
+        &self,
+        renamed: &[(PathBuf, PathBuf)],
+    ) -> HashMap<Url, Vec<TextEdit>> {
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        let Ok(root) = std::fs::canonicalize(&self.config.templates) else {
+            return changes;
+        };
+        for uri in self.documents.keys() {
+            let references = self.template_references(uri);
+            for reference in references {
+                let Some(path) = self.template_path(&reference.name) else {
+                    continue;
+                };
+                let Some(new_name) = renamed_template(&root, &path, renamed) else {
+                    continue;
+                };
+                let Ok(url) = Url::parse(uri) else {
+                    continue;
+                };
+                changes
+                    .entry(url)
+                    .or_default()
+                    .push(TextEdit::new(template_path_range(&reference), new_name));
+            }
+        }
+        changes
+    }
+
+    pub fn will_rename_files(&self, params: RenameFilesParams) -> Option<WorkspaceEdit> {
+        let mut renamed = vec![];
+        for file in params.files {
+            let (Some(old), Some(new)) = (file_path(&file.old_uri), file_path(&file.new_uri))
+            else {
+                continue;
+            };
+            let Ok(old) = std::fs::canonicalize(old) else {
+                continue;
+            };
+            renamed.push((old, new));
+        }
+        let changes = self.template_reference_edits(&renamed);
+        if changes.is_empty() {
+            return None;
+        }
+        Some(WorkspaceEdit::new(changes))
+    }
+
+    /// Moves template to `new_name` and updates every reference to it.
+    fn move_template(&self, path: PathBuf, new_name: &str) -> Option<WorkspaceEdit> {
+        if new_name.is_empty() || new_name.contains(['"', '\'']) {
+            return None;
+        }
+        let mut new_path = std::fs::canonicalize(&self.config.templates).ok()?;
+        new_path.push(path_items(new_name));
+        let changes = self.template_reference_edits(&[(path.to_owned(), new_path.to_owned())]);
+        let mut operations = vec![];
+        for (uri, edits) in changes {
+            operations.push(DocumentChangeOperation::Edit(TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+                edits: edits.into_iter().map(OneOf::Left).collect(),
+            }));
+        }
+        operations.push(DocumentChangeOperation::Op(ResourceOp::Rename(
+            RenameFile {
+                old_uri: Url::from_file_path(path).ok()?,
+                new_uri: Url::from_file_path(new_path).ok()?,
+                options: Some(RenameFileOptions {
+                    overwrite: Some(false),
+                    ignore_if_exists: Some(false),
+                }),
+                annotation_id: None,
+            },
+        )));
+        Some(WorkspaceEdit {
+            changes: None,
+            document_changes: Some(DocumentChanges::Operations(operations)),
+            change_annotations: None,
+        })
+    }
+
+    /// Moves everything that server knows about renamed files to their new uri.
+    pub fn did_rename_files(&mut self, params: RenameFilesParams) -> Option<DiagnosticMessage> {
+        let mut hm = HashMap::new();
+        for file in params.files {
+            let old_uri = Url::parse(&file.old_uri).ok()?.to_string();
+            let new_uri = Url::parse(&file.new_uri).ok()?.to_string();
+            let renamed: Vec<(String, String)> = self
+                .documents
+                .keys()
+                .filter_map(|uri| {
+                    let rest = uri.strip_prefix(&old_uri)?;
+                    (rest.is_empty() || rest.starts_with('/'))
+                        .then(|| (uri.to_owned(), format!("{new_uri}{rest}")))
+                })
+                .collect();
+            for (old, new) in renamed {
+                if let Some(document) = self.documents.remove(&old) {
+                    self.documents.insert(new.to_owned(), document);
+                }
+                for trees in self.trees.values_mut() {
+                    if let Some(tree) = trees.remove(&old) {
+                        trees.insert(new.to_owned(), tree);
+                    }
+                }
+                if let Some(variables) = self.variables.remove(&old) {
+                    self.variables.insert(new.to_owned(), variables);
+                }
+                if let Some(code_actions) = self.code_actions.remove(&old) {
+                    self.code_actions.insert(new.to_owned(), code_actions);
+                }
+                if let Some(scope_errors) = self.scope_errors.remove(&old) {
+                    self.scope_errors.insert(new.to_owned(), scope_errors);
+                }
+                hm.insert(old, vec![]);
+                hm.insert(new, vec![]);
+            }
+        }
+        // names in graph point to old uris
+        self.graph = TemplateGraph::default();
+        self.contexts.clear();
+        self.context_names.clear();
+        let documents: Vec<String> = self.documents.keys().cloned().collect();
+        for uri in documents {
+            let (Some(lang_type), Some(content)) = (
+                self.config.file_ext(&Path::new(&uri)),
+                self.document_content(&uri),
+            ) else {
+                continue;
+            };
+            self.link_file(&uri, lang_type, &content);
+        }
+        let mut diags = HashMap::new();
+        self.read_trees(&mut diags);
+        for (uri, errors) in diags {
+            hm.insert(uri, errors);
+        }
+        Some(DiagnosticMessage::Errors(hm))
+    }
+
+    pub fn template_imports(&self, uri: &str) -> Option<Vec<Import>> {
+        let trees = self.trees.get(&LangType::Template)?;
+        let tree = trees.get(uri)?;
+        let content = self.document_content(uri)?;
+        let query = &self.queries.jinja_imports;
+        let imports = templates_query(query, tree, Point::default(), &content, true);
+        Some(imports.show())
+    }
+
+    /// All imports, in every template, that point to template `uri`.
+    fn imports_of(&self, uri: &str) -> Vec<(String, Import)> {
+        let mut all = vec![];
+        let mut templates: Vec<&String> = self
+            .graph
+            .dependents(uri)
+            .into_iter()
+            .map(|(template, _)| template)
+            .collect();
+        templates.dedup();
+        for template in templates {
+            for import in self.template_imports(template).unwrap_or_default() {
+                let points_to_uri = import.templates().iter().any(|name| {
+                    self.template_uri(&name.name)
+                        .is_some_and(|imported| imported == uri)
+                });
+                if points_to_uri {
+                    all.push((template.to_owned(), import));
+                }
+            }
+        }
+        all
+    }
+
+    /// Globals of every backend file.
+    fn globals(&self) -> HashSet<String> {
+        self.contexts
+            .values()
+            .flat_map(|context| context.globals.iter().map(|global| global.name.to_owned()))
+            .collect()
+    }
+
+    /// Names defined for template `uri` by backend, `None` if it isn't
+    /// rendered or if some call passes dynamic context.
+    fn template_context(&self, uri: &str) -> Option<HashSet<String>> {
+        self.graph.template_context(uri, &self.globals())
+    }
+
+    /// Names that template `uri` expects from backend.
+    fn rendered_variables(&self, uri: &str) -> Vec<String> {
+        let mut names = vec![];
+        for template in self.graph.rendered_templates(uri) {
+            for name in self.context_names.get(&template).into_iter().flatten() {
+                if !names.contains(name) {
+                    names.push(name.to_owned());
+                }
+            }
+        }
+        names
+    }
+
+    /// Render calls in backend file `uri` that pass known context, with names
+    /// that rendered templates expect.
+    fn checked_render_calls(&self, uri: &str) -> Vec<(RenderCall, Vec<String>)> {
+        let mut calls = vec![];
+        for (template, call) in self.graph.calls_of(uri) {
+            if call.dynamic {
+                continue;
+            }
+            let names = self.rendered_variables(template);
+            calls.push((call.to_owned(), names));
+        }
+        calls
+    }
+
+    /// Render calls in backend file `uri` with variables that rendered
+    /// templates use, but call doesn't pass.
+    pub fn missing_context(&self, uri: &str) -> Vec<(RenderCall, Vec<String>)> {
+        let globals = self.globals();
+        let mut missing = vec![];
+        for (call, used) in self.checked_render_calls(uri) {
+            let names = missing_names(&call, &used, &globals);
+            if !names.is_empty() {
+                missing.push((call, names));
+            }
+        }
+        missing
+    }
+
+    /// Context keys in backend file `uri` that rendered templates never read.
+    pub fn unused_context(&self, uri: &str) -> Vec<Identifier> {
+        let mut unused = vec![];
+        for (call, used) in self.checked_render_calls(uri) {
+            unused.extend(unused_keys(&call, &used).into_iter().cloned());
+        }
+        unused
+    }
+
+    /// Lens at the top of template for every kind of place that uses it.
+    /// `jinja-lsp.showReferences` is command of VS Code extension that opens
+    /// `locations`, other clients get lenses with count only.
+    pub fn code_lens(&self, params: CodeLensParams) -> Option<Vec<CodeLens>> {
+        let uri = params.text_document.uri;
+        if self.config.file_ext(&Path::new(uri.as_str()))? != LangType::Template {
+            return None;
+        }
+        let dependents = template_dependents(&self.graph, uri.as_str());
+        let top = Position::new(0, 0);
+        let lenses = dependent_lenses(dependents)
+            .into_iter()
+            .map(|(title, locations)| {
+                let (command, arguments) = if self.is_vscode {
+                    let arguments = vec![
+                        serde_json::to_value(&uri).unwrap_or_default(),
+                        serde_json::to_value(top).unwrap_or_default(),
+                        serde_json::to_value(locations).unwrap_or_default(),
+                    ];
+                    (String::from("jinja-lsp.showReferences"), Some(arguments))
+                } else {
+                    (String::new(), None)
+                };
+                CodeLens {
+                    range: Range::new(top, top),
+                    command: Some(Command {
+                        title,
+                        command,
+                        arguments,
+                    }),
+                    data: None,
+                }
+            })
+            .collect();
+        Some(lenses)
+    }
+
+    fn type_hierarchy_item(&self, uri: &str) -> Option<TypeHierarchyItem> {
+        let url = Url::parse(uri).ok()?;
+        let root = std::fs::canonicalize(&self.config.templates).ok()?;
+        let path = url.to_file_path().ok()?;
+        let name =
+            template_name(&root, &path).or_else(|| Some(path.file_name()?.to_str()?.to_owned()))?;
+        let start = Range::new(Position::new(0, 0), Position::new(0, 0));
+        Some(TypeHierarchyItem {
+            name,
+            kind: SymbolKind::FILE,
+            tags: None,
+            detail: None,
+            uri: url,
+            range: start,
+            selection_range: start,
+            data: None,
+        })
+    }
+
+    /// Template under the cursor when it is in `{% extends %}` (or other
+    /// template path), current template otherwise.
+    pub fn prepare_type_hierarchy(
+        &self,
+        params: TypeHierarchyPrepareParams,
+    ) -> Option<Vec<TypeHierarchyItem>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .as_str();
+        let position = params.text_document_position_params.position;
+        let point = Point::new(position.line as usize, position.character as usize);
+        if self.config.file_ext(&Path::new(uri))? != LangType::Template {
+            return None;
+        }
+        let template = match self.template_at(uri, point) {
+            Some(template) => self.template_uri(&template.name)?,
+            None => uri.to_owned(),
+        };
+        Some(vec![self.type_hierarchy_item(&template)?])
+    }
+
+    /// Template that is extended by template from `item`.
+    pub fn supertypes(
+        &self,
+        params: TypeHierarchySupertypesParams,
+    ) -> Option<Vec<TypeHierarchyItem>> {
+        let parents = self
+            .graph
+            .parents(params.item.uri.as_str())
+            .into_iter()
+            .filter_map(|parent| self.type_hierarchy_item(parent))
+            .collect();
+        Some(parents)
+    }
+
+    /// Templates that extend template from `item`.
+    pub fn subtypes(&self, params: TypeHierarchySubtypesParams) -> Option<Vec<TypeHierarchyItem>> {
+        let children = self
+            .graph
+            .children(params.item.uri.as_str())
+            .into_iter()
+            .filter_map(|child| self.type_hierarchy_item(child))
+            .collect();
+        Some(children)
+    }
+
+    /// Block definition with name `name` in template `uri`.
+    fn template_block(&self, uri: &str, name: &str) -> Option<Location> {
+        let block = self.variables.get(uri)?.iter().find(|variable| {
+            variable.identifier_type == IdentifierType::TemplateBlock && variable.name == name
+        })?;
+        let url = Url::parse(uri).ok()?;
+        Some(Location::new(url, to_range((block.start, block.end))))
+    }
+
+    /// Same block in closest template up the `{% extends %}` chain.
+    fn parent_block(&self, uri: &str, point: Point) -> Option<Location> {
+        let block = self
+            .block_names(uri)
+            .into_iter()
+            .find(|block| block.start <= point && point <= block.end)?;
+        let has_block = |template: &str| self.template_block(template, &block.name).is_some();
+        let parent = self.graph.parent_block(uri, has_block)?;
+        self.template_block(&parent, &block.name)
+    }
+
+    /// First definition of block under the cursor and every override of it
+    /// in templates that extend it.
+    fn block_overrides(&self, uri: &str, point: Point) -> Option<Vec<Location>> {
+        if self.config.file_ext(&Path::new(uri))? != LangType::Template {
+            return None;
+        }
+        let block = self
+            .block_names(uri)
+            .into_iter()
+            .find(|block| block.start <= point && point <= block.end)?;
+        let has_block = |template: &str| self.template_block(template, &block.name).is_some();
+        let overrides = self
+            .graph
+            .block_overrides(uri, has_block)
+            .iter()
+            .filter_map(|template| self.template_block(template, &block.name))
+            .collect();
+        Some(overrides)
+    }
+
+    /// Names of `{% block name %}` and `{% endblock name %}` statements.
+    fn block_names(&self, uri: &str) -> Vec<Identifier> {
+        let Some(tree) = self
+            .trees
+            .get(&LangType::Template)
+            .and_then(|trees| trees.get(uri))
+        else {
+            return vec![];
+        };
+        let Some(content) = self.document_content(uri) else {
+            return vec![];
+        };
+        let query = &self.queries.jinja_definitions;
+        let definitions = definition_query(query, tree, Point::default(), &content, true);
+        let mut blocks: Vec<Identifier> = definitions
+            .collect()
+            .into_iter()
+            .filter(|identifier| identifier.identifier_type == IdentifierType::TemplateBlock)
+            .collect();
+        blocks.extend(definitions.block_ends);
+        blocks
+    }
+
+    fn document_content(&self, uri: &str) -> Option<String> {
+        let rope = self.documents.get(uri)?;
+        let mut writter = FileContent::default();
+        let _ = rope.write_to(&mut writter);
+        Some(writter.content)
+    }
+
+    pub fn delete_documents(&mut self) {
+        self.documents.clear();
+    }
+
+    pub fn delete_documents_with_id(&mut self, id: String) {
+        let mut ids = vec![];
+        for i in &self.documents {
+            if i.0.contains(&id) {
+                ids.push(i.0.clone());
+            }
+        }
+        for i in ids {
+            self.documents.remove(&i);
+            self.variables.remove(&i);
+            if let Some(templates) = self.trees.get_mut(&LangType::Template) {
+                templates.remove(&i);
+            }
+        }
+    }
+}
+
+impl Default for LspFiles {
+    fn default() -> Self {
+        let mut trees = HashMap::new();
+        trees.insert(LangType::Template, HashMap::new());
+        trees.insert(LangType::Backend, HashMap::new());
+        let diagnostics_task = None;
+        let main_channel = None;
+        Self {
+            trees,
+            parsers: Parsers::default(),
+            queries: Queries::default(),
+            documents: HashMap::new(),
+            config: JinjaConfig::default(),
+            diagnostics_task,
+            main_channel,
+            variables: HashMap::default(),
+            is_vscode: false,
+            code_actions: HashMap::default(),
+            ignore_globals: false,
+            warn_unused: false,
+            filters: vec![],
+            scope_errors: HashMap::default(),
+            graph: TemplateGraph::default(),
+            contexts: HashMap::default(),
+            context_names: HashMap::default(),
+        }
+    }
+}
+
+impl Clone for LspFiles {
+    fn clone(&self) -> Self {
+        let trees = self.trees.clone();
+        let parsers = Parsers::default();
+        let queries = Queries::default();
+        let documents = self.documents.clone();
+        let main_channel = self.main_channel.clone();
+        let variables = self.variables.clone();
+        let is_vscode = self.is_vscode;
+        let code_actions = self.code_actions.clone();
+        let config = self.config.clone();
+        let scope_errors = self.scope_errors.clone();
+        let task = None;
+        Self {
+            trees,
+            documents,
+            parsers,
+            queries,
+            config,
+            main_channel,
+            variables,
+            code_actions,
+            is_vscode,
+            diagnostics_task: task,
+            ignore_globals: self.ignore_globals,
+            warn_unused: self.warn_unused,
+            filters: self.filters.clone(),
+            scope_errors,
+            graph: self.graph.clone(),
+            contexts: self.contexts.clone(),
+            context_names: self.context_names.clone(),
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct FileContent {
+    pub content: String,
+}
+
+impl std::io::Write for FileContent {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Ok(b) = std::str::from_utf8(buf) {
+            self.content.push_str(b);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct FileWriter {
+    pub content: String,
+}
+
+impl std::io::Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Ok(b) = std::str::from_utf8(buf) {
+            self.content.push_str(b);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn dedup_locations(references: &mut Vec<Location>) {
+    references.sort_by(|a, b| {
+        a.uri
+            .as_str()
+            .cmp(b.uri.as_str())
+            .then(a.range.start.line.cmp(&b.range.start.line))
+            .then(a.range.start.character.cmp(&b.range.start.character))
+            .then(a.range.end.line.cmp(&b.range.end.line))
+            .then(a.range.end.character.cmp(&b.range.end.character))
+    });
+    references.dedup();
+}
+
+pub enum JinjaCodeAction {
+    Reset,
     CreateTemplate(String),
+    /// Single edit in current document, with title.
+    QuickFix(String, TextEdit),
+}
+
+enum RenameTarget {
+    /// Variable, macro or macro parameter defined in template `uri`.
+    Definition { uri: String, definition: Identifier },
+    /// Local name from `{% from ... import name as alias %}`.
+    Alias {
+        uri: String,
+        alias: String,
+        location: (Point, Point),
+    },
+    /// Block that is shared by all templates in the same `extends` chain.
+    Block { uri: String, name: String },
+    /// Template path string, renaming it moves the template file.
+    Template { name: String, path: PathBuf },
+}
+
+/// Call hierarchy item for calls made outside of any macro.
+fn file_call_hierarchy_item(uri: &str) -> Option<CallHierarchyItem> {
+    let url = Url::parse(uri).ok()?;
+    let file = Path::new(url.path()).file_name()?.to_str()?.to_owned();
+    let start = Range::new(Position::new(0, 0), Position::new(0, 0));
+    Some(CallHierarchyItem {
+        name: file,
+        kind: SymbolKind::FILE,
+        tags: None,
+        detail: None,
+        uri: url,
+        range: start,
+        selection_range: start,
+        data: None,
+    })
+}
+
+/// Byte offset of LSP position, where character is in UTF-16 code units.
+fn position_byte(rope: &Rope, position: Position) -> Option<usize> {
+    let line = rope.try_line_to_char(position.line as usize).ok()?;
+    let line = rope.try_char_to_utf16_cu(line).ok()?;
+    let char = rope
+        .try_utf16_cu_to_char(line + position.character as usize)
+        .ok()?;
+    rope.try_char_to_byte(char).ok()
+}
+
+/// Globals, filters and tests that backend file registers.
+fn registered_names(context: &BackendContext) -> [Vec<String>; 3] {
+    [&context.globals, &context.filters, &context.tests]
+        .map(|names| names.iter().map(|name| name.name.to_owned()).collect())
+}
+
+/// Canonical path of template `name` in `templates` directory.
+fn template_file_path(templates: &Path, name: &str) -> Option<PathBuf> {
+    std::fs::canonicalize(templates.join(path_items(name))).ok()
+}
+
+/// Uri of template `name` in `templates` directory.
+fn template_file_uri(templates: &Path, name: &str) -> Option<String> {
+    let path = template_file_path(templates, name)?;
+    let url = Url::parse(&format!("file://{}", path.to_str()?)).ok()?;
+    Some(url.to_string())
+}
+
+/// Path of `file://` uri.
+fn file_path(uri: &str) -> Option<PathBuf> {
+    Url::parse(uri).ok()?.to_file_path().ok()
+}
+
+fn byte_point(rope: &Rope, byte: usize) -> Option<Point> {
+    let line = rope.try_byte_to_line(byte).ok()?;
+    let column = byte - rope.try_line_to_byte(line).ok()?;
+    Some(Point::new(line, column))
+}
+
+/// Path of file relative to working directory of server.
+fn relative_path(uri: &str) -> String {
+    let Some(path) = Url::parse(uri).ok().and_then(|url| url.to_file_path().ok()) else {
+        return uri.to_owned();
+    };
+    let relative = std::env::current_dir()
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok().map(PathBuf::from));
+    relative.unwrap_or(path).display().to_string()
 }
@@ -1,3 +1,4 @@
+use jinja_lsp_queries::builtins::builtin_tests;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -15,38 +16,58 @@ impl From<(&str, &str)> for TemplateTestCompletion {
     }
 }
 
-pub fn init_template_test_completions() -> Vec<TemplateTestCompletion> {
-    vec![
-        TemplateTestCompletion::from(("boolean", include_str!("md/is_boolean.md"))),
-        TemplateTestCompletion::from(("defined", include_str!("md/is_defined.md"))),
-        TemplateTestCompletion::from(("divisibleby", include_str!("md/is_divisibleby.md"))),
-        TemplateTestCompletion::from(("endingwith", include_str!("md/is_endingwith.md"))),
-        TemplateTestCompletion::from(("eq", include_str!("md/is_eq.md"))),
-        TemplateTestCompletion::from(("even", include_str!("md/is_even.md"))),
-        TemplateTestCompletion::from(("false", include_str!("md/is_false.md"))),
-        TemplateTestCompletion::from(("filter", include_str!("md/is_filter.md"))),
-        TemplateTestCompletion::from(("float", include_str!("md/is_float.md"))),
-        TemplateTestCompletion::from(("ge", include_str!("md/is_ge.md"))),
-        TemplateTestCompletion::from(("gt", include_str!("md/is_gt.md"))),
-        TemplateTestCompletion::from(("in", include_str!("md/is_in.md"))),
-        TemplateTestCompletion::from(("integer", include_str!("md/is_integer.md"))),
-        TemplateTestCompletion::from(("iterable", include_str!("md/is_iterable.md"))),
-        TemplateTestCompletion::from(("le", include_str!("md/is_le.md"))),
-        TemplateTestCompletion::from(("lower", include_str!("md/is_lower.md"))),
-        TemplateTestCompletion::from(("lt", include_str!("md/is_lt.md"))),
-        TemplateTestCompletion::from(("mapping", include_str!("md/is_mapping.md"))),
-        TemplateTestCompletion::from(("ne", include_str!("md/is_ne.md"))),
-        TemplateTestCompletion::from(("none", include_str!("md/is_none.md"))),
-        TemplateTestCompletion::from(("number", include_str!("md/is_number.md"))),
-        TemplateTestCompletion::from(("odd", include_str!("md/is_odd.md"))),
-        TemplateTestCompletion::from(("safe", include_str!("md/is_safe.md"))),
-        TemplateTestCompletion::from(("sameas", include_str!("md/is_sameas.md"))),
-        TemplateTestCompletion::from(("sequence", include_str!("md/is_sequence.md"))),
-        TemplateTestCompletion::from(("startingwith", include_str!("md/is_startingwith.md"))),
-        TemplateTestCompletion::from(("string", include_str!("md/is_string.md"))),
-        TemplateTestCompletion::from(("test", include_str!("md/is_test.md"))),
-        TemplateTestCompletion::from(("true", include_str!("md/is_true.md"))),
-        TemplateTestCompletion::from(("undefined", include_str!("md/is_undefined.md"))),
-        TemplateTestCompletion::from(("upper", include_str!("md/is_upper.md"))),
-    ]
+/// Documentation of built-in tests, aliases share it with their test.
+static TEST_DOCS: &[(&str, &str)] = &[
+    ("boolean", include_str!("md/is_boolean.md")),
+    ("defined", include_str!("md/is_defined.md")),
+    ("divisibleby", include_str!("md/is_divisibleby.md")),
+    ("endingwith", include_str!("md/is_endingwith.md")),
+    ("eq", include_str!("md/is_eq.md")),
+    ("equalto", include_str!("md/is_eq.md")),
+    ("even", include_str!("md/is_even.md")),
+    ("false", include_str!("md/is_false.md")),
+    ("filter", include_str!("md/is_filter.md")),
+    ("float", include_str!("md/is_float.md")),
+    ("ge", include_str!("md/is_ge.md")),
+    ("greaterthan", include_str!("md/is_gt.md")),
+    ("gt", include_str!("md/is_gt.md")),
+    ("in", include_str!("md/is_in.md")),
+    ("integer", include_str!("md/is_integer.md")),
+    ("iterable", include_str!("md/is_iterable.md")),
+    ("le", include_str!("md/is_le.md")),
+    ("lessthan", include_str!("md/is_lt.md")),
+    ("lower", include_str!("md/is_lower.md")),
+    ("lt", include_str!("md/is_lt.md")),
+    ("mapping", include_str!("md/is_mapping.md")),
+    ("ne", include_str!("md/is_ne.md")),
+    ("none", include_str!("md/is_none.md")),
+    ("number", include_str!("md/is_number.md")),
+    ("odd", include_str!("md/is_odd.md")),
+    ("safe", include_str!("md/is_safe.md")),
+    ("sameas", include_str!("md/is_sameas.md")),
+    ("sequence", include_str!("md/is_sequence.md")),
+    ("startingwith", include_str!("md/is_startingwith.md")),
+    ("string", include_str!("md/is_string.md")),
+    ("test", include_str!("md/is_test.md")),
+    ("true", include_str!("md/is_true.md")),
+    ("undefined", include_str!("md/is_undefined.md")),
+    ("upper", include_str!("md/is_upper.md")),
+];
+
+/// Built-in tests of template engine that backend `lang` uses, the same ones
+/// that templates are checked against.
+pub fn init_template_test_completions(lang: &str) -> Vec<TemplateTestCompletion> {
+    builtin_tests(lang)
+        .iter()
+        .map(|name| {
+            let desc = TEST_DOCS.iter().find(|(test, _)| test == name).map_or_else(
+                || format!("Built-in test `{name}`."),
+                |(_, desc)| desc.to_string(),
+            );
+            TemplateTestCompletion {
+                name: name.to_string(),
+                desc,
+            }
+        })
+        .collect()
 }